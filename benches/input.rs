@@ -0,0 +1,37 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use hex::input::Input;
+use hex::{CanonicalWriter, LineWriter, RangeOptions};
+use std::{fs, io};
+
+const SIZE: usize = 4 << 20;
+
+fn input_paths(c: &mut Criterion) {
+    let path = std::env::temp_dir().join(format!("hex-bench-input-{}.bin", std::process::id()));
+    let data: Vec<u8> = (0..SIZE).map(|i| (i * 31 % 251) as u8).collect();
+    fs::write(&path, &data).unwrap();
+
+    let range = RangeOptions {
+        length: None,
+        skip: None,
+        decompress: false,
+    };
+    let writers: Vec<Box<dyn LineWriter>> = vec![Box::new(CanonicalWriter::default())];
+
+    let mut group = c.benchmark_group("input");
+    group.throughput(Throughput::Bytes(SIZE as u64));
+    group.sample_size(10);
+    for &(name, mmap) in &[("mmap", true), ("stream", false)] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let (mut input, offset) = Input::open(&path, &range, mmap).unwrap();
+                input.print(&writers, offset, &mut io::sink()).unwrap();
+            })
+        });
+    }
+    group.finish();
+
+    fs::remove_file(&path).unwrap();
+}
+
+criterion_group!(benches, input_paths);
+criterion_main!(benches);
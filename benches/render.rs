@@ -0,0 +1,30 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use hex::{print_slice_parallel, print_slice_to, CanonicalWriter, LineWriter, OneByteChar};
+use std::io;
+
+const SIZE: usize = 16 << 20;
+
+fn writers(c: &mut Criterion) {
+    let data: Vec<u8> = (0..SIZE).map(|i| (i * 31 % 251) as u8).collect();
+
+    let mut group = c.benchmark_group("render");
+    group.throughput(Throughput::Bytes(SIZE as u64));
+    group.sample_size(10);
+    let canonical: Vec<Box<dyn LineWriter>> = vec![Box::new(CanonicalWriter::default())];
+    group.bench_function("canonical", |b| {
+        b.iter(|| print_slice_to(&canonical, 0, &data, &mut io::sink()).unwrap())
+    });
+    let chars: Vec<Box<dyn LineWriter>> = vec![Box::new(OneByteChar::default())];
+    group.bench_function("char", |b| {
+        b.iter(|| print_slice_to(&chars, 0, &data, &mut io::sink()).unwrap())
+    });
+    let make_writers =
+        || -> Vec<Box<dyn LineWriter>> { vec![Box::new(CanonicalWriter::default())] };
+    group.bench_function("canonical-4-threads", |b| {
+        b.iter(|| print_slice_parallel(&make_writers, 0, &data, 4, &mut io::sink()).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, writers);
+criterion_main!(benches);
@@ -0,0 +1,262 @@
+//! Reading single members of tar and zip archives without extracting them.
+
+use crate::decompress;
+use crate::formats::zip::{method_name, ZipArchive};
+use crate::formats::FormatError;
+use crate::magic;
+use std::fs;
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATE: u16 = 8;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ArchiveKind {
+    Tar,
+    Zip,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Member {
+    pub path: String,
+    /// Size of the member's contents, after any decompression.
+    pub size: u64,
+    /// Where the member's bytes are stored verbatim in the archive, if they are.
+    pub data_offset: Option<u64>,
+    /// Where the member's bytes start in the tar stream, after any decompression,
+    /// for tar members.
+    pub stream_offset: Option<u64>,
+    /// The zip compression method, for zip members.
+    pub method: Option<u16>,
+}
+
+/// Split an input of the form `ARCHIVE:MEMBER` where `ARCHIVE` is an existing file.
+/// Inputs naming an existing file are never split.
+pub fn split_member_spec(input: &Path) -> Option<(PathBuf, String)> {
+    if input.exists() {
+        return None;
+    }
+    let text = input.to_str()?;
+    text.match_indices(':').find_map(|(at, _)| {
+        let archive = Path::new(&text[..at]);
+        if archive.is_file() && at + 1 < text.len() {
+            Some((archive.to_path_buf(), text[at + 1..].to_string()))
+        } else {
+            None
+        }
+    })
+}
+
+fn open_file(path: &Path) -> io::Result<(io::BufReader<fs::File>, ArchiveKind)> {
+    let mut reader = io::BufReader::new(fs::File::open(path)?);
+    let is_zip = magic::identify(reader.fill_buf()?)
+        .iter()
+        .any(|m| m.name == "zip");
+    let kind = if is_zip {
+        ArchiveKind::Zip
+    } else {
+        ArchiveKind::Tar
+    };
+    Ok((reader, kind))
+}
+
+/// Open a tar stream, decompressing it first if asked to.
+fn tar_reader(reader: io::BufReader<fs::File>, decompress: bool) -> io::Result<Box<dyn Read>> {
+    if decompress {
+        decompress::auto_decoder(reader)
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+fn tar_path(entry: &tar::Entry<impl Read>) -> String {
+    String::from_utf8_lossy(&entry.path_bytes()).into_owned()
+}
+
+fn zip_member(entry: &crate::formats::zip::Entry) -> Member {
+    Member {
+        path: entry.name(),
+        size: entry.uncompressed_size,
+        data_offset: match entry.method {
+            METHOD_STORED => entry.data_range().map(|(offset, _)| offset),
+            _ => None,
+        },
+        stream_offset: None,
+        method: Some(entry.method),
+    }
+}
+
+/// Every member of the archive at `path`, in archive order.
+pub fn list_members(path: &Path, decompress: bool) -> Result<Vec<Member>, FormatError> {
+    let (mut reader, kind) = open_file(path)?;
+    match kind {
+        ArchiveKind::Zip => {
+            let archive = ZipArchive::parse(&mut reader)?;
+            Ok(archive.entries.iter().map(zip_member).collect())
+        }
+        ArchiveKind::Tar => {
+            let mut archive = tar::Archive::new(tar_reader(reader, decompress)?);
+            let mut members = vec![];
+            for entry in archive.entries()? {
+                let entry = entry?;
+                members.push(Member {
+                    path: tar_path(&entry),
+                    size: entry.size(),
+                    // A decompressed stream's positions aren't offsets in the archive.
+                    data_offset: Some(entry.raw_file_position()).filter(|_| !decompress),
+                    stream_offset: Some(entry.raw_file_position()),
+                    method: None,
+                });
+            }
+            Ok(members)
+        }
+    }
+}
+
+/// Stream the contents of member `name` of the archive at `path`.
+pub fn open_member(
+    path: &Path,
+    name: &str,
+    decompress: bool,
+) -> Result<(Box<dyn Read>, Member), FormatError> {
+    let (mut reader, kind) = open_file(path)?;
+    match kind {
+        ArchiveKind::Zip => {
+            let archive = ZipArchive::parse(&mut reader)?;
+            let entry = archive
+                .entry(name)
+                .ok_or_else(|| FormatError::NoEntry(name.to_string()))?;
+            let (offset, len) = crate::formats::zip::entry_range(&archive, name)?;
+            reader.seek(SeekFrom::Start(offset))?;
+            let raw = reader.take(len);
+            let contents: Box<dyn Read> = match entry.method {
+                METHOD_STORED => Box::new(raw),
+                METHOD_DEFLATE => Box::new(flate2::read::DeflateDecoder::new(raw)),
+                other => {
+                    return Err(FormatError::Malformed(format!(
+                        "entry {:?}: {} compression is not supported",
+                        name,
+                        method_name(other)
+                    )))
+                }
+            };
+            Ok((contents, zip_member(entry)))
+        }
+        ArchiveKind::Tar => {
+            let member = list_members(path, decompress)?
+                .into_iter()
+                .find(|m| m.path.trim_start_matches("./") == name.trim_start_matches("./"))
+                .ok_or_else(|| FormatError::NoEntry(name.to_string()))?;
+            // Entries borrow their archive, so find the member's position first and
+            // then read it from a fresh stream.
+            let offset = member.stream_offset.unwrap_or(0);
+            let (mut reader, _) = open_file(path)?;
+            let contents: Box<dyn Read> = if decompress {
+                let mut stream = tar_reader(reader, true)?;
+                io::copy(&mut (&mut stream).take(offset), &mut io::sink())?;
+                stream
+            } else {
+                reader.seek(SeekFrom::Start(offset))?;
+                Box::new(reader)
+            };
+            Ok((Box::new(contents.take(member.size)), member))
+        }
+    }
+}
+
+pub fn print_members(members: &[Member], w: &mut dyn Write) -> io::Result<()> {
+    for member in members {
+        match member.data_offset {
+            Some(offset) => write!(w, "{:#010x}\t", offset)?,
+            None => write!(w, "{:<10}\t", "-")?,
+        }
+        write!(w, "{:>10}\t", member.size)?;
+        if let Some(method) = member.method {
+            write!(w, "{:<8}\t", method_name(method))?;
+        }
+        writeln!(w, "{}", member.path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tar_bytes(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(vec![]);
+        for (path, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, *data).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn tar_file(entries: &[(&str, &[u8])]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("hex-archive-{}.tar", std::process::id()));
+        fs::write(&path, tar_bytes(entries)).unwrap();
+        path
+    }
+
+    #[test]
+    fn tar_members() {
+        let path = tar_file(&[("a.txt", b"alpha"), ("dir/b.bin", &[7u8; 600])]);
+        let members = list_members(&path, false).unwrap();
+        let summary: Vec<(&str, u64, Option<u64>)> = members
+            .iter()
+            .map(|m| (m.path.as_str(), m.size, m.data_offset))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![("a.txt", 5, Some(512)), ("dir/b.bin", 600, Some(1536))]
+        );
+
+        let (mut reader, member) = open_member(&path, "dir/b.bin", false).unwrap();
+        assert_eq!(member.data_offset, Some(1536));
+        let mut data = vec![];
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, vec![7u8; 600]);
+
+        let err = open_member(&path, "missing", false).err().unwrap();
+        assert_eq!(err.to_string(), "no entry named \"missing\"");
+
+        let spec = format!("{}:dir/b.bin", path.display());
+        assert_eq!(
+            split_member_spec(Path::new(&spec)),
+            Some((path.clone(), "dir/b.bin".to_string()))
+        );
+        assert_eq!(split_member_spec(&path), None);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn compressed_tar_members() {
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        encoder
+            .write_all(&tar_bytes(&[("a.txt", b"alpha"), ("b.txt", b"beta")]))
+            .unwrap();
+        let path = std::env::temp_dir().join(format!("hex-archive-{}.tar.gz", std::process::id()));
+        fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+        let members = list_members(&path, true).unwrap();
+        let summary: Vec<(&str, Option<u64>, Option<u64>)> = members
+            .iter()
+            .map(|m| (m.path.as_str(), m.data_offset, m.stream_offset))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![("a.txt", None, Some(512)), ("b.txt", None, Some(1536))]
+        );
+
+        let (mut reader, member) = open_member(&path, "b.txt", true).unwrap();
+        assert_eq!(member.data_offset, None);
+        let mut data = vec![];
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"beta");
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -75,4 +75,6 @@ pub enum ByteError {
     ValueIncorrect(#[from] ValueIncorrectError),
     #[error(transparent)]
     UnitIncorrect(#[from] UnitIncorrectError),
+    #[error("the block size must be from 1 B to 1 GiB")]
+    BlockSizeOutOfRange,
 }
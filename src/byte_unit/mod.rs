@@ -348,6 +348,20 @@ pub fn parse_bytes<S: AsRef<str>>(s: S) -> Result<u128, ByteError> {
     Ok(b.0)
 }
 
+/// Largest block size `parse_block_size` accepts, and the most a format string may
+/// consume per row. It fits a `usize` on every target, and a row of format string
+/// output is buffered whole.
+pub const MAX_BLOCK_SIZE: usize = GIBIBYTE as usize;
+
+/// Parse the size of a block of input, which must be nonzero and at most
+/// `MAX_BLOCK_SIZE`.
+pub fn parse_block_size<S: AsRef<str>>(s: S) -> Result<usize, ByteError> {
+    match parse_bytes(s)? {
+        size @ 1.. if size <= MAX_BLOCK_SIZE as u128 => Ok(size as usize),
+        _ => Err(ByteError::BlockSizeOutOfRange),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -370,4 +384,16 @@ mod tests {
         assert_eq!(parse_bytes("12.5  KiB").unwrap(), 12_800);
         assert_eq!(parse_bytes("1.25 K").unwrap(), 1_280);
     }
+
+    #[test]
+    fn block_sizes() {
+        assert_eq!(parse_block_size("4KiB").unwrap(), 4096);
+        assert_eq!(parse_block_size("1GiB").unwrap(), MAX_BLOCK_SIZE);
+        for size in &["0", "1.5GiB", "1EiB"] {
+            assert_eq!(
+                parse_block_size(size).unwrap_err().to_string(),
+                "the block size must be from 1 B to 1 GiB"
+            );
+        }
+    }
 }
@@ -0,0 +1,374 @@
+//! Decoding of the text pane in character sets other than ASCII.
+
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Charset {
+    #[default]
+    Ascii,
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+    /// EBCDIC code page 037, as used on IBM mainframes in the US.
+    Ebcdic,
+    /// The IBM PC character set, with its glyphs for control codes.
+    Cp437,
+}
+
+#[derive(Debug, Clone, Error)]
+#[error(
+    "unknown charset {0:?}; expected ascii, utf-8, utf-16le, utf-16be, latin-1, ebcdic or cp437"
+)]
+pub struct UnknownCharsetError(String);
+
+impl FromStr for Charset {
+    type Err = UnknownCharsetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ascii" => Ok(Charset::Ascii),
+            "utf-8" | "utf8" => Ok(Charset::Utf8),
+            "utf-16" | "utf16" | "utf-16le" | "utf16le" => Ok(Charset::Utf16Le),
+            "utf-16be" | "utf16be" => Ok(Charset::Utf16Be),
+            "latin-1" | "latin1" | "iso-8859-1" => Ok(Charset::Latin1),
+            "ebcdic" | "cp037" => Ok(Charset::Ebcdic),
+            "cp437" => Ok(Charset::Cp437),
+            _ => Err(UnknownCharsetError(s.to_string())),
+        }
+    }
+}
+
+/// What the text pane shows for one byte.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Glyph {
+    /// A character starting at this byte.
+    Char(char),
+    /// A control character starting at this byte.
+    Control(char),
+    /// A later byte of a character starting before it.
+    Continuation,
+    /// A byte that isn't part of any character.
+    Invalid(u8),
+}
+
+/// The text pane's mark for continuation bytes.
+const CONTINUATION: char = '·';
+
+/// How the text pane shows control characters and bytes that don't decode.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Nonprintable {
+    /// `.` for all of them.
+    #[default]
+    Dot,
+    /// `^@`, `^J` and `^?`, with `M-` in front of high bytes, like `cat -v`.
+    Caret,
+    /// The Unicode control pictures, such as `␀` and `␊`.
+    Pictures,
+    /// `⋄` for NUL, `_` for whitespace, `•` for other controls and `×` for high bytes,
+    /// like `hexyl`.
+    Glyphs,
+}
+
+#[derive(Debug, Clone, Error)]
+#[error("unknown rendering {0:?}; expected dot, caret, pictures or glyphs")]
+pub struct UnknownNonprintableError(String);
+
+impl FromStr for Nonprintable {
+    type Err = UnknownNonprintableError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(Nonprintable::Dot),
+            "caret" => Ok(Nonprintable::Caret),
+            "pictures" => Ok(Nonprintable::Pictures),
+            "glyphs" => Ok(Nonprintable::Glyphs),
+            _ => Err(UnknownNonprintableError(s.to_string())),
+        }
+    }
+}
+
+impl Nonprintable {
+    /// Append how `glyph` is shown to `text`.
+    pub fn push(self, glyph: Glyph, text: &mut String) {
+        let byte = match glyph {
+            Glyph::Char(c) => return text.push(c),
+            Glyph::Continuation => return text.push(CONTINUATION),
+            // Controls are all below U+00A0.
+            Glyph::Control(c) => c as u8,
+            Glyph::Invalid(byte) => byte,
+        };
+        match self {
+            Nonprintable::Dot => text.push('.'),
+            Nonprintable::Caret => {
+                if byte >= 0x80 {
+                    text.push_str("M-");
+                }
+                match byte & 0x7f {
+                    0x7f => text.push_str("^?"),
+                    low @ 0..=0x1f => {
+                        text.push('^');
+                        text.push((low + 0x40) as char);
+                    }
+                    low => text.push(low as char),
+                }
+            }
+            Nonprintable::Pictures => text.push(match byte {
+                0..=0x1f => char::from_u32(0x2400 + u32::from(byte)).unwrap(),
+                0x7f => '␡',
+                _ => '.',
+            }),
+            Nonprintable::Glyphs => text.push(match glyph {
+                Glyph::Control('\0') => '⋄',
+                Glyph::Control('\t' | '\n' | '\u{b}' | '\u{c}' | '\r') => '_',
+                Glyph::Control(c) if c.is_ascii() => '•',
+                _ => '×',
+            }),
+        }
+    }
+}
+
+fn glyph(c: char) -> Glyph {
+    if c.is_control() {
+        Glyph::Control(c)
+    } else {
+        Glyph::Char(c)
+    }
+}
+
+impl Charset {
+    /// One glyph for each byte of `row`. Rows are decoded on their own, so the tail of
+    /// a character split across rows is shown as continuation bytes, and its head as
+    /// invalid.
+    pub fn decode(self, row: &[u8]) -> Vec<Glyph> {
+        match self {
+            Charset::Ascii => row
+                .iter()
+                .map(|&byte| match byte {
+                    0..=0x7f => glyph(byte as char),
+                    _ => Glyph::Invalid(byte),
+                })
+                .collect(),
+            Charset::Latin1 => row.iter().map(|&byte| glyph(byte as char)).collect(),
+            Charset::Ebcdic => row
+                .iter()
+                .map(|&byte| glyph(EBCDIC[byte as usize]))
+                .collect(),
+            Charset::Cp437 => row
+                .iter()
+                .map(|&byte| glyph(CP437[byte as usize]))
+                .collect(),
+            Charset::Utf8 => decode_utf8(row),
+            Charset::Utf16Le => decode_utf16(row, u16::from_le_bytes),
+            Charset::Utf16Be => decode_utf16(row, u16::from_be_bytes),
+        }
+    }
+}
+
+fn decode_utf8(row: &[u8]) -> Vec<Glyph> {
+    let mut glyphs = Vec::with_capacity(row.len());
+    // Continuation bytes at the start of the row finish a character from the row before.
+    let head = row
+        .iter()
+        .take(3)
+        .take_while(|&&byte| byte & 0xc0 == 0x80)
+        .count();
+    glyphs.resize(head, Glyph::Continuation);
+    let mut rest = &row[head..];
+    while let Some(&lead) = rest.first() {
+        let len = match lead {
+            0xc2..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf4 => 4,
+            _ => 1,
+        };
+        let decoded = rest
+            .get(..len)
+            .and_then(|bytes| std::str::from_utf8(bytes).ok())
+            .and_then(|s| s.chars().next());
+        match decoded {
+            Some(c) => {
+                glyphs.push(glyph(c));
+                glyphs.resize(glyphs.len() + len - 1, Glyph::Continuation);
+                rest = &rest[len..];
+            }
+            None => {
+                glyphs.push(Glyph::Invalid(lead));
+                rest = &rest[1..];
+            }
+        }
+    }
+    glyphs
+}
+
+fn decode_utf16(row: &[u8], unit: fn([u8; 2]) -> u16) -> Vec<Glyph> {
+    let mut glyphs = Vec::with_capacity(row.len());
+    let units: Vec<u16> = row
+        .chunks_exact(2)
+        .map(|pair| unit([pair[0], pair[1]]))
+        .collect();
+    let mut i = 0;
+    while i < units.len() {
+        let (decoded, len) = match units[i] {
+            // The low half of a surrogate pair from the row before.
+            0xdc00..=0xdfff if i == 0 => (Some(Glyph::Continuation), 1),
+            high @ 0xd800..=0xdbff => match units.get(i + 1) {
+                Some(&low @ 0xdc00..=0xdfff) => {
+                    let c =
+                        0x10000 + ((u32::from(high) - 0xd800) << 10) + (u32::from(low) - 0xdc00);
+                    (char::from_u32(c).map(glyph), 2)
+                }
+                _ => (None, 1),
+            },
+            unit => (char::from_u32(u32::from(unit)).map(glyph), 1),
+        };
+        match decoded {
+            Some(glyph) => {
+                glyphs.push(glyph);
+                glyphs.resize(glyphs.len() + len * 2 - 1, Glyph::Continuation);
+            }
+            None => glyphs.extend(
+                row[i * 2..i * 2 + 2]
+                    .iter()
+                    .map(|&byte| Glyph::Invalid(byte)),
+            ),
+        }
+        i += len;
+    }
+    if row.len() % 2 == 1 {
+        glyphs.push(Glyph::Invalid(row[row.len() - 1]));
+    }
+    glyphs
+}
+
+/// Code page 037 to Unicode.
+static EBCDIC: [char; 256] = [
+    '\u{0}', '\u{1}', '\u{2}', '\u{3}', '\u{9c}', '\u{9}', '\u{86}', '\u{7f}', '\u{97}', '\u{8d}',
+    '\u{8e}', '\u{b}', '\u{c}', '\u{d}', '\u{e}', '\u{f}', '\u{10}', '\u{11}', '\u{12}', '\u{13}',
+    '\u{9d}', '\u{85}', '\u{8}', '\u{87}', '\u{18}', '\u{19}', '\u{92}', '\u{8f}', '\u{1c}',
+    '\u{1d}', '\u{1e}', '\u{1f}', '\u{80}', '\u{81}', '\u{82}', '\u{83}', '\u{84}', '\u{a}',
+    '\u{17}', '\u{1b}', '\u{88}', '\u{89}', '\u{8a}', '\u{8b}', '\u{8c}', '\u{5}', '\u{6}',
+    '\u{7}', '\u{90}', '\u{91}', '\u{16}', '\u{93}', '\u{94}', '\u{95}', '\u{96}', '\u{4}',
+    '\u{98}', '\u{99}', '\u{9a}', '\u{9b}', '\u{14}', '\u{15}', '\u{9e}', '\u{1a}', ' ', ' ', 'â',
+    'ä', 'à', 'á', 'ã', 'å', 'ç', 'ñ', '¢', '.', '<', '(', '+', '|', '&', 'é', 'ê', 'ë', 'è', 'í',
+    'î', 'ï', 'ì', 'ß', '!', '$', '*', ')', ';', '¬', '-', '/', 'Â', 'Ä', 'À', 'Á', 'Ã', 'Å', 'Ç',
+    'Ñ', '¦', ',', '%', '_', '>', '?', 'ø', 'É', 'Ê', 'Ë', 'È', 'Í', 'Î', 'Ï', 'Ì', '`', ':', '#',
+    '@', '\'', '=', '"', 'Ø', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', '«', '»', 'ð', 'ý', 'þ',
+    '±', '°', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 'ª', 'º', 'æ', '¸', 'Æ', '¤', 'µ', '~',
+    's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '¡', '¿', 'Ð', 'Ý', 'Þ', '®', '^', '£', '¥', '·', '©',
+    '§', '¶', '¼', '½', '¾', '[', ']', '¯', '¨', '´', '×', '{', 'A', 'B', 'C', 'D', 'E', 'F', 'G',
+    'H', 'I', '\u{ad}', 'ô', 'ö', 'ò', 'ó', 'õ', '}', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R',
+    '¹', 'û', 'ü', 'ù', 'ú', 'ÿ', '\\', '÷', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '²', 'Ô', 'Ö',
+    'Ò', 'Ó', 'Õ', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', '³', 'Û', 'Ü', 'Ù', 'Ú',
+    '\u{9f}',
+];
+
+/// Code page 437 to Unicode, with the glyphs shown for control codes.
+static CP437: [char; 256] = [
+    '\u{0}', '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼', '►', '◄',
+    '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼', ' ', '!', '"', '#', '$',
+    '%', '&', '\'', '(', ')', '*', '+', ',', '-', '.', '/', '0', '1', '2', '3', '4', '5', '6', '7',
+    '8', '9', ':', ';', '<', '=', '>', '?', '@', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J',
+    'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '[', '\\', ']',
+    '^', '_', '`', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p',
+    'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '{', '|', '}', '~', '⌂', 'Ç', 'ü', 'é', 'â',
+    'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', 'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û',
+    'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', 'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐',
+    '¬', '½', '¼', '¡', '«', '»', '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝',
+    '╜', '╛', '┐', '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀', 'α', 'ß', 'Γ',
+    'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', '≡', '±', '≥', '≤', '⌠', '⌡',
+    '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', ' ',
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Glyph::*;
+
+    #[test]
+    fn single_byte_charsets() {
+        assert_eq!(
+            Charset::Ebcdic.decode(b"\xc8\x85\x93\x25\xff"),
+            [
+                Char('H'),
+                Char('e'),
+                Char('l'),
+                Control('\n'),
+                Control('\u{9f}')
+            ]
+        );
+        assert_eq!(
+            Charset::Cp437.decode(b"\x01\xdb\x00"),
+            [Char('☺'), Char('█'), Control('\0')]
+        );
+        assert_eq!(
+            Charset::Latin1.decode(b"\xe9\x85"),
+            [Char('é'), Control('\u{85}')]
+        );
+        assert_eq!(
+            Charset::Ascii.decode(b"a\n\xe9"),
+            [Char('a'), Control('\n'), Invalid(0xe9)]
+        );
+    }
+
+    #[test]
+    fn nonprintables() {
+        let glyphs = Charset::Latin1.decode(b"\0\n\x1b\x7f\x85\xe9");
+        let show = |style: Nonprintable| {
+            let mut text = String::new();
+            for &glyph in &glyphs {
+                style.push(glyph, &mut text);
+            }
+            text
+        };
+        assert_eq!(show(Nonprintable::Dot), ".....é");
+        assert_eq!(show(Nonprintable::Caret), "^@^J^[^?M-^Eé");
+        assert_eq!(show(Nonprintable::Pictures), "␀␊␛␡.é");
+        assert_eq!(show(Nonprintable::Glyphs), "⋄_••×é");
+
+        let mut text = String::new();
+        Nonprintable::Caret.push(Invalid(0xc1), &mut text);
+        Nonprintable::Glyphs.push(Invalid(0xc1), &mut text);
+        assert_eq!(text, "M-A×");
+    }
+
+    #[test]
+    fn multibyte_charsets() {
+        // The tail of a character from the row before, a two-byte character, a stray
+        // continuation byte, and the head of a character cut off by the end of the row.
+        assert_eq!(
+            Charset::Utf8.decode(b"\xa4a\xc3\xa4\x80\xe2\x82"),
+            [
+                Continuation,
+                Char('a'),
+                Char('ä'),
+                Continuation,
+                Invalid(0x80),
+                Invalid(0xe2),
+                Invalid(0x82)
+            ]
+        );
+        assert_eq!(
+            Charset::Utf16Le.decode(b"\x00\xdcA\x00\x3d\xd8\x00\xde\x3d\xd8!"),
+            [
+                Continuation,
+                Continuation,
+                Char('A'),
+                Continuation,
+                Char('😀'),
+                Continuation,
+                Continuation,
+                Continuation,
+                Invalid(0x3d),
+                Invalid(0xd8),
+                Invalid(b'!')
+            ]
+        );
+        assert_eq!(
+            Charset::Utf16Be.decode(b"\x00\x0a"),
+            [Control('\n'), Continuation]
+        );
+    }
+}
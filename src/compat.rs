@@ -0,0 +1,212 @@
+//! Profiles reproducing the output of `hexdump -C`, `xxd` and `od` byte for byte.
+
+use crate::format_string::{self, FormatWriter};
+use crate::od::{self, AddressRadix, OdType};
+use crate::{render, ChunkData, LineWriter};
+use std::cell::{Cell, RefCell};
+use std::io::{self, Write};
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Compat {
+    Hexdump,
+    Xxd,
+    Od,
+}
+
+#[derive(Debug, Clone, Error)]
+#[error("unknown tool {0:?}; expected hexdump, xxd or od")]
+pub struct UnknownToolError(String);
+
+impl FromStr for Compat {
+    type Err = UnknownToolError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hexdump" => Ok(Compat::Hexdump),
+            "xxd" => Ok(Compat::Xxd),
+            "od" => Ok(Compat::Od),
+            _ => Err(UnknownToolError(s.to_string())),
+        }
+    }
+}
+
+/// The format strings behind `hexdump -C`.
+const HEXDUMP_CANONICAL: [&str; 3] = [
+    r#""%08.8_Ax\n""#,
+    r#""%08.8_ax  " 8/1 "%02x " "  " 8/1 "%02x ""#,
+    r#""  |" 16/1 "%_p" "|\n""#,
+];
+
+impl Compat {
+    /// Whether the tool replaces repeated rows with `*` unless given `-v`.
+    pub fn squeezes(self) -> bool {
+        self != Compat::Xxd
+    }
+
+    pub fn writer(self) -> Box<dyn LineWriter> {
+        match self {
+            Compat::Hexdump => {
+                let strings = HEXDUMP_CANONICAL
+                    .iter()
+                    .map(|source| format_string::parse(source).expect("built-in format string"))
+                    .collect();
+                Box::new(FormatWriter::new(strings).expect("built-in format strings"))
+            }
+            Compat::Xxd => Box::new(XxdWriter),
+            Compat::Od => od::writers(&[OdType::DEFAULT], AddressRadix::Octal, 16)
+                .expect("od defaults")
+                .remove(0),
+        }
+    }
+}
+
+/// `xxd` rows: hex in groups of two bytes, then the text.
+pub struct XxdWriter;
+
+impl LineWriter for XxdWriter {
+    fn print_idx(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()> {
+        write!(w, "{:08x}: ", data.offset)
+    }
+
+    fn print_chunk(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()> {
+        const TEXT: usize = 16 / 2 * 5 + 1;
+        let pad = data.pad.min(16);
+        let chunk = &data.chunk[..data.chunk.len().min(16 - pad)];
+        let mut line = [b' '; TEXT + 16 + 1];
+        for (i, &byte) in (pad..).zip(chunk) {
+            let at = i * 2 + i / 2;
+            line[at..at + 2].copy_from_slice(&render::HEX_LOWER[byte as usize]);
+            line[TEXT + i] = render::PRINTABLE[byte as usize];
+        }
+        let end = TEXT + pad + chunk.len();
+        line[end] = b'\n';
+        w.write_all(&line[..end + 1])
+    }
+}
+
+/// Replaces runs of full rows equal to the row before them with a single `*` line,
+/// the way `hexdump` and `od` do unless given `-v`. The writers are shown as a group.
+pub struct Squeeze {
+    inner: Vec<Box<dyn LineWriter>>,
+    previous: RefCell<Vec<u8>>,
+    /// Whether the current row is a repeat, and so is not shown.
+    repeat: Cell<bool>,
+    /// Whether the `*` for the current run of repeats was written.
+    starred: Cell<bool>,
+}
+
+impl Squeeze {
+    pub fn new(inner: Vec<Box<dyn LineWriter>>) -> Self {
+        Squeeze {
+            inner,
+            previous: RefCell::new(vec![]),
+            repeat: Cell::new(false),
+            starred: Cell::new(false),
+        }
+    }
+}
+
+impl LineWriter for Squeeze {
+    fn print_idx(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()> {
+        let mut previous = self.previous.borrow_mut();
+        let repeat = data.chunk.len() == self.row_size() && *previous == data.chunk;
+        self.repeat.set(repeat);
+        if repeat {
+            if !self.starred.replace(true) {
+                w.write_all(b"*\n")?;
+            }
+            return Ok(());
+        }
+        self.starred.set(false);
+        previous.clear();
+        previous.extend_from_slice(data.chunk);
+        Ok(())
+    }
+
+    fn print_chunk(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()> {
+        if self.repeat.get() {
+            return Ok(());
+        }
+        for writer in &self.inner {
+            writer.print_idx(data, w)?;
+            writer.print_chunk(data, w)?;
+        }
+        Ok(())
+    }
+
+    fn row_size(&self) -> usize {
+        self.inner
+            .first()
+            .map_or(crate::ROW_SIZE, |writer| writer.row_size())
+    }
+
+    fn print_end(&self, start: u128, end: u128, w: &mut dyn Write) -> io::Result<()> {
+        for writer in &self.inner {
+            writer.print_end(start, end, w)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{print_slice_to, EntropyGutter};
+
+    const SAMPLE: &[u8] = include_bytes!("../tests/fixtures/compat/sample.bin");
+
+    fn render(compat: Compat, data: &[u8]) -> String {
+        let mut writer = compat.writer();
+        if compat.squeezes() {
+            writer = Box::new(Squeeze::new(vec![writer]));
+        }
+        let mut out = vec![];
+        print_slice_to(&[writer], 0, data, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn matches_golden_files() {
+        let golden = [
+            (
+                Compat::Xxd,
+                include_str!("../tests/fixtures/compat/sample.xxd"),
+            ),
+            (
+                Compat::Od,
+                include_str!("../tests/fixtures/compat/sample.od"),
+            ),
+            (
+                Compat::Hexdump,
+                include_str!("../tests/fixtures/compat/sample.hexdump"),
+            ),
+        ];
+        for &(compat, expected) in &golden {
+            assert_eq!(render(compat, SAMPLE), expected, "{:?}", compat);
+        }
+    }
+
+    #[test]
+    fn squeezed_rows_get_no_entropy_gutter() {
+        let squeeze = Squeeze::new(vec![Compat::Hexdump.writer()]);
+        let writers: Vec<Box<dyn LineWriter>> =
+            vec![Box::new(EntropyGutter::new(Box::new(squeeze), 16))];
+        let mut out = vec![];
+        print_slice_to(&writers, 0, &[0; 64], &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "00000000  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|\t▁ 0.00\n\
+             *\n\
+             00000040\n"
+        );
+    }
+
+    #[test]
+    fn empty_input() {
+        assert_eq!(render(Compat::Xxd, b""), "");
+        assert_eq!(render(Compat::Hexdump, b""), "");
+        assert_eq!(render(Compat::Od, b""), "0000000\n");
+    }
+}
@@ -0,0 +1,178 @@
+//! Transparent decompression of gzip, zstd, xz and bzip2 input.
+
+use crate::magic;
+use std::fmt;
+use std::io::{self, BufRead, Read};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+    Bzip2,
+}
+
+impl Compression {
+    pub const ALL: [Compression; 4] = [
+        Compression::Gzip,
+        Compression::Zstd,
+        Compression::Xz,
+        Compression::Bzip2,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Xz => "xz",
+            Compression::Bzip2 => "bzip2",
+        }
+    }
+
+    /// The compression format of a stream starting with `head`, by magic number.
+    pub fn detect(head: &[u8]) -> Option<Compression> {
+        magic::identify(head).iter().find_map(|m| {
+            Compression::ALL
+                .iter()
+                .copied()
+                .find(|c| c.name() == m.name)
+        })
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A decoder that reports failures with the format and decompressed position, and
+/// keeps reporting them, so that callers may first consume what was decoded.
+struct Decoder {
+    inner: Box<dyn Read>,
+    compression: Compression,
+    position: u128,
+    error: Option<(io::ErrorKind, String)>,
+}
+
+impl Read for Decoder {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some((kind, ref message)) = self.error {
+            return Err(io::Error::new(kind, message.clone()));
+        }
+        match self.inner.read(buf) {
+            Ok(n) => {
+                self.position += n as u128;
+                Ok(n)
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => Err(e),
+            Err(e) => {
+                let kind = match e.kind() {
+                    io::ErrorKind::UnexpectedEof => io::ErrorKind::UnexpectedEof,
+                    _ => io::ErrorKind::InvalidData,
+                };
+                let message = format!(
+                    "{} stream is truncated or corrupt after {} decompressed bytes: {}",
+                    self.compression, self.position, e
+                );
+                self.error = Some((kind, message.clone()));
+                Err(io::Error::new(kind, message))
+            }
+        }
+    }
+}
+
+/// Wrap `reader` in a decoder for `compression`. Concatenated gzip, xz and bzip2
+/// members, and zstd frames, are decoded as one stream.
+pub fn decoder<R: BufRead + 'static>(
+    reader: R,
+    compression: Compression,
+) -> io::Result<Box<dyn Read>> {
+    let inner: Box<dyn Read> = match compression {
+        Compression::Gzip => Box::new(flate2::bufread::MultiGzDecoder::new(reader)),
+        Compression::Zstd => Box::new(zstd::Decoder::with_buffer(reader)?),
+        Compression::Xz => Box::new(xz2::bufread::XzDecoder::new_multi_decoder(reader)),
+        Compression::Bzip2 => Box::new(bzip2::bufread::MultiBzDecoder::new(reader)),
+    };
+    Ok(Box::new(Decoder {
+        inner,
+        compression,
+        position: 0,
+        error: None,
+    }))
+}
+
+/// Detect the compression of `reader` from its first bytes and wrap it in a decoder.
+pub fn auto_decoder<R: BufRead + 'static>(mut reader: R) -> io::Result<Box<dyn Read>> {
+    let compression = Compression::detect(reader.fill_buf()?).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "input is not compressed with gzip, zstd, xz or bzip2",
+        )
+    })?;
+    decoder(reader, compression)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const TEXT: &[u8] = b"the quick brown fox jumps over the lazy dog\n";
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn decode_all(data: Vec<u8>) -> io::Result<Vec<u8>> {
+        let mut out = vec![];
+        auto_decoder(io::Cursor::new(data))?.read_to_end(&mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn round_trip_every_format() {
+        let compressed = vec![
+            gzip(TEXT),
+            zstd::encode_all(TEXT, 3).unwrap(),
+            {
+                let mut e = xz2::write::XzEncoder::new(vec![], 6);
+                e.write_all(TEXT).unwrap();
+                e.finish().unwrap()
+            },
+            {
+                let mut e = bzip2::write::BzEncoder::new(vec![], bzip2::Compression::default());
+                e.write_all(TEXT).unwrap();
+                e.finish().unwrap()
+            },
+        ];
+        for (data, expected) in compressed.into_iter().zip(Compression::ALL.iter()) {
+            assert_eq!(Compression::detect(&data), Some(*expected));
+            assert_eq!(decode_all(data).unwrap(), TEXT);
+        }
+
+        let err = decode_all(b"plain".to_vec()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn multi_member_and_truncated_gzip() {
+        let mut data = gzip(b"first ");
+        data.extend(gzip(b"second"));
+        assert_eq!(decode_all(data).unwrap(), b"first second");
+
+        let mut data = gzip(&TEXT.repeat(10));
+        data.truncate(data.len() - 12);
+        let mut reader = auto_decoder(io::Cursor::new(data)).unwrap();
+        let mut out = vec![];
+        let err = reader.read_to_end(&mut out).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(err
+            .to_string()
+            .starts_with("gzip stream is truncated or corrupt after"));
+        // The error sticks, so a reader that stopped short still sees it.
+        assert!(reader.read(&mut [0; 16]).is_err());
+    }
+}
@@ -0,0 +1,126 @@
+//! Block and character devices: telling them apart from regular files, finding the size
+//! of a block device, and reading one in aligned blocks.
+
+use crate::read_to_fill;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Reads of block devices start on, and cover, multiples of this many bytes, which
+/// suits devices with sectors of up to 4 KiB even when opened with `O_DIRECT`.
+pub const ALIGN: usize = 4096;
+/// Bytes read from a block device at a time.
+const BLOCK_SIZE: usize = 256 << 10;
+
+#[cfg(unix)]
+pub fn is_block_device(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    metadata.file_type().is_block_device()
+}
+
+#[cfg(not(unix))]
+pub fn is_block_device(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+/// Whether a file can be read from any offset: a regular file or a block device.
+pub fn is_seekable(metadata: &fs::Metadata) -> bool {
+    metadata.is_file() || is_block_device(metadata)
+}
+
+/// The size of the regular file or block device at `path`. Block devices report a
+/// length of zero, so they are asked by seeking to their end. Anything else, like a
+/// pipe or a character device, has no size.
+pub fn size(path: &Path) -> io::Result<Option<u64>> {
+    let metadata = fs::metadata(path)?;
+    if metadata.is_file() {
+        Ok(Some(metadata.len()))
+    } else if is_block_device(&metadata) {
+        let mut file = fs::File::open(path)?;
+        file.seek(SeekFrom::End(0)).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+/// Reads a block device from any offset in aligned blocks, into an aligned buffer.
+pub struct AlignedReader {
+    file: fs::File,
+    buffer: Vec<u8>,
+    /// Where the aligned part of `buffer` starts.
+    base: usize,
+    /// The unread part of the block in the buffer.
+    start: usize,
+    end: usize,
+}
+
+impl AlignedReader {
+    /// Read `file` from `skip`, starting at the multiple of `ALIGN` before it.
+    pub fn new(mut file: fs::File, skip: u64) -> io::Result<Self> {
+        let aligned = skip - skip % ALIGN as u64;
+        file.seek(SeekFrom::Start(aligned))?;
+        let buffer = vec![0u8; BLOCK_SIZE + ALIGN];
+        let base = buffer.as_ptr().align_offset(ALIGN).min(ALIGN);
+        let mut reader = AlignedReader {
+            file,
+            buffer,
+            base,
+            start: 0,
+            end: 0,
+        };
+        // Drop the bytes before `skip` from the first block.
+        let head = (skip - aligned) as usize;
+        if head > 0 {
+            reader.fill()?;
+            reader.start = head.min(reader.end);
+        }
+        Ok(reader)
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        let block = &mut self.buffer[self.base..self.base + BLOCK_SIZE];
+        self.end = read_to_fill(&mut self.file, block)?;
+        self.start = 0;
+        Ok(())
+    }
+}
+
+impl Read for AlignedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.start == self.end {
+            self.fill()?;
+        }
+        let block = &self.buffer[self.base + self.start..self.base + self.end];
+        let n = buf.len().min(block.len());
+        buf[..n].copy_from_slice(&block[..n]);
+        self.start += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligned_reads_from_any_offset() {
+        let path = std::env::temp_dir().join(format!("hex-device-{}.bin", std::process::id()));
+        let data: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(&path, &data).unwrap();
+        assert_eq!(size(&path).unwrap(), Some(20_000));
+
+        for &skip in &[0, 1, 4096, 5000, 19_999, 20_000, 30_000] {
+            let file = fs::File::open(&path).unwrap();
+            let mut reader = AlignedReader::new(file, skip).unwrap();
+            let mut rest = vec![];
+            reader.read_to_end(&mut rest).unwrap();
+            assert_eq!(
+                rest,
+                &data[(skip as usize).min(data.len())..],
+                "skip {}",
+                skip
+            );
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
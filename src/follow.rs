@@ -0,0 +1,184 @@
+//! Following a file as it grows, like `tail -f`.
+
+use crate::{print_rows, row_size, LineWriter};
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How long to wait before looking for more data.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Why following started over from the beginning of the file.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Restart {
+    Truncated,
+    /// The path now names a different file, as after log rotation.
+    Replaced,
+}
+
+impl fmt::Display for Restart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Restart::Truncated => write!(f, "file truncated; starting over"),
+            Restart::Replaced => write!(f, "file replaced; starting over"),
+        }
+    }
+}
+
+pub struct Follow {
+    path: PathBuf,
+    file: fs::File,
+    /// Offset in the file of the first byte of `pending`.
+    position: u64,
+    /// Offset shown for the first byte of the file.
+    origin: u128,
+    /// The bytes of a row that isn't complete yet.
+    pending: Vec<u8>,
+    /// The length of `pending` at the previous poll.
+    polled: usize,
+    /// The length of `pending` when it was last shown as a partial row.
+    shown: usize,
+}
+
+impl Follow {
+    /// Start following `path` from `skip`, showing that byte's offset as `offset`.
+    pub fn open(path: &Path, skip: u64, offset: u128) -> io::Result<Follow> {
+        let mut file = fs::File::open(path)?;
+        file.seek(SeekFrom::Start(skip))?;
+        Ok(Follow {
+            path: path.to_owned(),
+            file,
+            position: skip,
+            origin: offset.saturating_sub(u128::from(skip)),
+            pending: vec![],
+            polled: 0,
+            shown: 0,
+        })
+    }
+
+    /// Render the rows completed since the last poll. A partial row is shown once it
+    /// stops growing for a poll, and again in full when it's complete. If the file was
+    /// truncated or replaced, the rest of the old file is dropped, following starts
+    /// over at its beginning, and the reason is returned.
+    pub fn poll(
+        &mut self,
+        writers: &[Box<dyn LineWriter>],
+        w: &mut dyn Write,
+    ) -> io::Result<Option<Restart>> {
+        if let Some(restart) = self.restart()? {
+            return Ok(Some(restart));
+        }
+
+        let row_size = row_size(writers);
+        let mut buf = [0u8; 64 << 10];
+        loop {
+            let amt = match self.file.read(&mut buf) {
+                Ok(0) => break,
+                Ok(amt) => amt,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            self.pending.extend_from_slice(&buf[..amt]);
+            let rows = self.pending.len() - self.pending.len() % row_size;
+            print_rows(writers, self.offset(), &self.pending[..rows], w)?;
+            self.position += rows as u64;
+            self.pending.drain(..rows);
+            if rows > 0 {
+                self.polled = 0;
+                self.shown = 0;
+            }
+        }
+
+        let len = self.pending.len();
+        if len > 0 && len == self.polled && len != self.shown {
+            print_rows(writers, self.offset(), &self.pending, w)?;
+            self.shown = len;
+        }
+        self.polled = len;
+        Ok(None)
+    }
+
+    fn offset(&self) -> u128 {
+        self.origin + u128::from(self.position)
+    }
+
+    /// Go back to the start of the file if it shrank or was replaced.
+    fn restart(&mut self) -> io::Result<Option<Restart>> {
+        // The path may be missing for a moment while the file is rotated.
+        let metadata = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            Err(_) => return Ok(None),
+        };
+        let restart = if !same_file(&metadata, &self.file.metadata()?) {
+            self.file = fs::File::open(&self.path)?;
+            Restart::Replaced
+        } else if metadata.is_file() && metadata.len() < self.position + self.pending.len() as u64 {
+            self.file.seek(SeekFrom::Start(0))?;
+            Restart::Truncated
+        } else {
+            return Ok(None);
+        };
+        self.position = 0;
+        self.pending.clear();
+        self.polled = 0;
+        self.shown = 0;
+        Ok(Some(restart))
+    }
+}
+
+#[cfg(unix)]
+fn same_file(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    (a.dev(), a.ino()) == (b.dev(), b.ino())
+}
+
+#[cfg(not(unix))]
+fn same_file(_a: &fs::Metadata, _b: &fs::Metadata) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CanonicalWriter;
+    use std::fs::OpenOptions;
+
+    #[test]
+    fn follows_growth_and_truncation() {
+        let path = std::env::temp_dir().join(format!("hex-follow-{}.bin", std::process::id()));
+        fs::write(&path, b"0123456789abcdefXYZ").unwrap();
+        let writers: Vec<Box<dyn LineWriter>> = vec![Box::new(CanonicalWriter::default())];
+        let mut follow = Follow::open(&path, 1, 1).unwrap();
+        let mut poll = || {
+            let mut out = vec![];
+            let restart = follow.poll(&writers, &mut out).unwrap();
+            (String::from_utf8(out).unwrap(), restart)
+        };
+        let append = |data: &[u8]| {
+            let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+            file.write_all(data).unwrap();
+        };
+
+        let (out, _) = poll();
+        assert!(out.starts_with("0x00000001\t31 32"));
+        assert_eq!(out.lines().count(), 1);
+        // The partial row is shown once it stops growing, and only once.
+        let (out, _) = poll();
+        assert!(out.starts_with("0x00000011\t59 5A "));
+        assert!(out.ends_with("\t|YZ|\n"));
+        assert_eq!(poll().0, "");
+        append(b"!");
+        assert_eq!(poll().0, "");
+        assert!(poll().0.ends_with("\t|YZ!|\n"));
+        append(b"...............");
+        assert!(poll().0.ends_with("\t|YZ!.............|\n"));
+
+        fs::write(&path, b"new").unwrap();
+        assert_eq!(poll(), (String::new(), Some(Restart::Truncated)));
+        assert_eq!(poll().0, "");
+        assert!(poll().0.starts_with("0x00000000\t6E 65 77"));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,813 @@
+//! Format strings in the language of BSD and util-linux `hexdump -e`.
+//!
+//! A format string is a list of units, each an optional iteration count, an optional
+//! byte count after a `/`, and a quoted `printf`-style format:
+//!
+//! ```text
+//! "%08.8_ax  " 8/1 "%02x " "  " 8/1 "%02x " "  |" 16/1 "%_p" "|\n"
+//! ```
+//!
+//! Each conversion consumes the unit's byte count, or by default four bytes for
+//! integers and one for characters. Besides `printf`'s `d i o u x X c s`, there are
+//! `%_a[dox]` and `%_A[dox]` for the offset of the next byte and of the end of the
+//! input, `%_c` for characters with C escapes, `%_p` for printable characters or `.`
+//! and `%_u` for ASCII control names. Every format string is applied to each block of
+//! input, whose size is that of the largest.
+
+use crate::{byte_unit::MAX_BLOCK_SIZE, ChunkData, LineWriter};
+use byteorder::{ByteOrder, NativeEndian};
+use std::io::{self, Write};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum FormatStringError {
+    #[error(
+        "{}column {column}: {message}",
+        .line.map_or_else(String::new, |line| format!("line {}, ", line))
+    )]
+    Syntax {
+        line: Option<usize>,
+        column: usize,
+        message: String,
+    },
+    #[error("the format strings display no input bytes")]
+    Empty,
+}
+
+impl FormatStringError {
+    /// The line of `source` the error is on, with a caret under the offending column.
+    pub fn context(&self, source: &str) -> Option<String> {
+        match *self {
+            FormatStringError::Syntax { line, column, .. } => {
+                let text = source.lines().nth(line.unwrap_or(1) - 1)?;
+                Some(format!("{}\n{:>width$}", text, "^", width = column))
+            }
+            FormatStringError::Empty => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    /// `%d`, `%i`
+    Signed,
+    /// `%o`, `%u`, `%x`, `%X`
+    Unsigned { radix: u32, upper: bool },
+    /// `%c`
+    Char,
+    /// `%s`
+    Str,
+    /// `%_a`
+    Address { radix: u32 },
+    /// `%_A`
+    EndAddress { radix: u32 },
+    /// `%_c`
+    Escaped,
+    /// `%_p`
+    Printable,
+    /// `%_u`
+    AsciiName,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Spec {
+    left: bool,
+    zero: bool,
+    plus: bool,
+    space: bool,
+    alt: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+enum Piece {
+    Text(Vec<u8>),
+    Conversion { spec: Spec, kind: Kind, size: usize },
+}
+
+#[derive(Clone, Debug)]
+struct Unit {
+    reps: usize,
+    /// Whether the iteration count was given, rather than left to fill the block.
+    explicit_reps: bool,
+    /// The byte count, if given.
+    count: Option<usize>,
+    pieces: Vec<Piece>,
+}
+
+impl Unit {
+    /// Bytes consumed by one iteration.
+    fn size(&self) -> usize {
+        self.checked_size()
+            .expect("unit sizes are checked when parsed")
+    }
+
+    /// Like `size`, or `None` if the sum overflows.
+    fn checked_size(&self) -> Option<usize> {
+        match self.count {
+            Some(count) => Some(count),
+            None => self
+                .pieces
+                .iter()
+                .try_fold(0usize, |sum, piece| match piece {
+                    Piece::Conversion { size, .. } => sum.checked_add(*size),
+                    Piece::Text(_) => Some(sum),
+                }),
+        }
+    }
+
+    fn shows_end(&self) -> bool {
+        self.pieces.iter().any(|piece| {
+            matches!(
+                piece,
+                Piece::Conversion {
+                    kind: Kind::EndAddress { .. },
+                    ..
+                }
+            )
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FormatString {
+    units: Vec<Unit>,
+}
+
+impl FormatString {
+    /// Bytes consumed by the whole string.
+    fn size(&self) -> usize {
+        self.units.iter().map(|unit| unit.reps * unit.size()).sum()
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    at: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T, S: Into<String>>(&self, at: usize, message: S) -> Result<T, FormatStringError> {
+        Err(FormatStringError::Syntax {
+            line: None,
+            column: self.source[..at].chars().count() + 1,
+            message: message.into(),
+        })
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.source.as_bytes().get(self.at).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+            self.at += 1;
+        }
+    }
+
+    fn number(&mut self) -> Result<Option<usize>, FormatStringError> {
+        let start = self.at;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.at += 1;
+        }
+        if start == self.at {
+            return Ok(None);
+        }
+        match self.source[start..self.at].parse() {
+            Ok(n) => Ok(Some(n)),
+            Err(_) => self.error(start, "number is too large"),
+        }
+    }
+
+    fn unit(&mut self) -> Result<Unit, FormatStringError> {
+        let start = self.at;
+        let reps = self.number()?;
+        if reps == Some(0) {
+            return self.error(start, "iteration count must be positive");
+        }
+        self.skip_whitespace();
+        let count = if self.peek() == Some(b'/') {
+            self.at += 1;
+            self.skip_whitespace();
+            let at = self.at;
+            match self.number()? {
+                Some(0) => return self.error(at, "byte count must be positive"),
+                Some(n) => Some(n),
+                None => return self.error(at, "expected a byte count after `/`"),
+            }
+        } else {
+            None
+        };
+        self.skip_whitespace();
+        if self.peek() != Some(b'"') {
+            return self.error(self.at, "expected a quoted format");
+        }
+        let quote = self.at;
+        self.at += 1;
+
+        let mut pieces = vec![];
+        let mut text = vec![];
+        loop {
+            match self.peek() {
+                None => return self.error(quote, "unterminated format"),
+                Some(b'"') => {
+                    self.at += 1;
+                    break;
+                }
+                Some(b'\\') => {
+                    self.at += 1;
+                    let c = match self.peek() {
+                        Some(c) => c,
+                        None => return self.error(quote, "unterminated format"),
+                    };
+                    text.push(match c {
+                        b'a' => 0x07,
+                        b'b' => 0x08,
+                        b'f' => 0x0c,
+                        b'n' => b'\n',
+                        b'r' => b'\r',
+                        b't' => b'\t',
+                        b'v' => 0x0b,
+                        other => other,
+                    });
+                    self.at += 1;
+                }
+                Some(b'%') if self.source.as_bytes().get(self.at + 1) == Some(&b'%') => {
+                    text.push(b'%');
+                    self.at += 2;
+                }
+                Some(b'%') => {
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(self.conversion(count)?);
+                }
+                Some(c) => {
+                    text.push(c);
+                    self.at += 1;
+                }
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+
+        let consuming = pieces
+            .iter()
+            .filter(|piece| match piece {
+                Piece::Conversion { kind, .. } => {
+                    !matches!(kind, Kind::Address { .. } | Kind::EndAddress { .. })
+                }
+                Piece::Text(_) => false,
+            })
+            .count();
+        if count.is_some() && consuming > 1 {
+            return self.error(
+                start,
+                "a byte count allows only one conversion besides `%_a` and `%_A`",
+            );
+        }
+        Ok(Unit {
+            reps: reps.unwrap_or(1),
+            explicit_reps: reps.is_some(),
+            count,
+            pieces,
+        })
+    }
+
+    /// Parse the conversion starting at the `%` under the cursor.
+    fn conversion(&mut self, count: Option<usize>) -> Result<Piece, FormatStringError> {
+        let percent = self.at;
+        self.at += 1;
+        let mut spec = Spec::default();
+        while let Some(c) = self.peek() {
+            match c {
+                b'-' => spec.left = true,
+                b'0' => spec.zero = true,
+                b'+' => spec.plus = true,
+                b' ' => spec.space = true,
+                b'#' => spec.alt = true,
+                _ => break,
+            }
+            self.at += 1;
+        }
+        spec.width = self.number()?.unwrap_or(0);
+        if self.peek() == Some(b'.') {
+            self.at += 1;
+            spec.precision = Some(self.number()?.unwrap_or(0));
+        }
+
+        let at = self.at;
+        let kind = match self.peek() {
+            None | Some(b'"') => return self.error(percent, "incomplete conversion"),
+            Some(b'*') => return self.error(at, "`*` widths and precisions are not supported"),
+            Some(b'd') | Some(b'i') => Kind::Signed,
+            Some(b'o') => Kind::Unsigned {
+                radix: 8,
+                upper: false,
+            },
+            Some(b'u') => Kind::Unsigned {
+                radix: 10,
+                upper: false,
+            },
+            Some(b'x') => Kind::Unsigned {
+                radix: 16,
+                upper: false,
+            },
+            Some(b'X') => Kind::Unsigned {
+                radix: 16,
+                upper: true,
+            },
+            Some(b'c') => Kind::Char,
+            Some(b's') => Kind::Str,
+            Some(b'e') | Some(b'E') | Some(b'f') | Some(b'g') | Some(b'G') => {
+                return self.error(at, "floating-point conversions are not supported")
+            }
+            Some(b'_') => {
+                self.at += 1;
+                match self.peek() {
+                    Some(c @ b'a') | Some(c @ b'A') => {
+                        self.at += 1;
+                        let radix = match self.peek() {
+                            Some(b'd') => 10,
+                            Some(b'o') => 8,
+                            Some(b'x') => 16,
+                            _ => {
+                                return self.error(
+                                    self.at,
+                                    format!("`%_{}` must be followed by d, o or x", c as char),
+                                )
+                            }
+                        };
+                        if c == b'a' {
+                            Kind::Address { radix }
+                        } else {
+                            Kind::EndAddress { radix }
+                        }
+                    }
+                    Some(b'c') => Kind::Escaped,
+                    Some(b'p') => Kind::Printable,
+                    Some(b'u') => Kind::AsciiName,
+                    _ => {
+                        return self.error(
+                            at,
+                            "bad conversion; expected `_a`, `_A`, `_c`, `_p` or `_u`",
+                        )
+                    }
+                }
+            }
+            Some(_) => {
+                let c = self.source[at..].chars().next().unwrap_or_default();
+                return self.error(at, format!("bad conversion character `{}`", c));
+            }
+        };
+        self.at += 1;
+
+        let size = match kind {
+            Kind::Signed | Kind::Unsigned { .. } => match count.unwrap_or(4) {
+                size @ (1 | 2 | 4 | 8) => size,
+                size => {
+                    return self.error(percent, format!("integers can't be {} bytes long", size))
+                }
+            },
+            Kind::Char | Kind::Escaped | Kind::Printable | Kind::AsciiName => match count {
+                None | Some(1) => 1,
+                Some(_) => return self.error(percent, "characters take a byte count of 1"),
+            },
+            Kind::Str => match count.or(spec.precision) {
+                Some(size) => size,
+                None => return self.error(percent, "`%s` needs a byte count or precision"),
+            },
+            Kind::Address { .. } | Kind::EndAddress { .. } => 0,
+        };
+        Ok(Piece::Conversion { spec, kind, size })
+    }
+}
+
+/// Parse one format string, as given to `hexdump -e`.
+pub fn parse(source: &str) -> Result<FormatString, FormatStringError> {
+    let mut parser = Parser { source, at: 0 };
+    let mut units = vec![];
+    let mut total = 0usize;
+    loop {
+        parser.skip_whitespace();
+        if parser.peek().is_none() {
+            return Ok(FormatString { units });
+        }
+        let start = parser.at;
+        let unit = parser.unit()?;
+        // A row holds all the bytes the string consumes.
+        total = match unit
+            .checked_size()
+            .and_then(|size| size.checked_mul(unit.reps))
+            .and_then(|size| size.checked_add(total))
+        {
+            Some(total) if total <= MAX_BLOCK_SIZE => total,
+            _ => return parser.error(start, "the format consumes more than 1 GiB at a time"),
+        };
+        units.push(unit);
+    }
+}
+
+/// Parse a format file, as given to `hexdump -f`: one format string per line, with
+/// blank lines and lines starting with `#` ignored.
+pub fn parse_file(source: &str) -> Result<Vec<FormatString>, FormatStringError> {
+    let mut strings = vec![];
+    for (number, line) in source.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        match parse(line) {
+            Ok(string) => strings.push(string),
+            Err(FormatStringError::Syntax {
+                column, message, ..
+            }) => {
+                return Err(FormatStringError::Syntax {
+                    line: Some(number + 1),
+                    column,
+                    message,
+                })
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(strings)
+}
+
+const ASCII_NAMES: [&str; 32] = [
+    "nul", "soh", "stx", "etx", "eot", "enq", "ack", "bel", "bs", "ht", "lf", "vt", "ff", "cr",
+    "so", "si", "dle", "dc1", "dc2", "dc3", "dc4", "nak", "syn", "etb", "can", "em", "sub", "esc",
+    "fs", "gs", "rs", "us",
+];
+
+/// Write `prefix` and `body` padded to the spec's width, with zeros between them if
+/// `zero` is set and spaces otherwise.
+fn pad(spec: &Spec, prefix: &[u8], body: &[u8], zero: bool, out: &mut Vec<u8>) {
+    let fill = spec.width.saturating_sub(prefix.len() + body.len());
+    if spec.left {
+        out.extend_from_slice(prefix);
+        out.extend_from_slice(body);
+        out.resize(out.len() + fill, b' ');
+    } else if zero {
+        out.extend_from_slice(prefix);
+        out.resize(out.len() + fill, b'0');
+        out.extend_from_slice(body);
+    } else {
+        out.resize(out.len() + fill, b' ');
+        out.extend_from_slice(prefix);
+        out.extend_from_slice(body);
+    }
+}
+
+fn integer(
+    spec: &Spec,
+    negative: bool,
+    magnitude: u128,
+    radix: u32,
+    upper: bool,
+    out: &mut Vec<u8>,
+) {
+    let digits: &[u8; 16] = if upper {
+        b"0123456789ABCDEF"
+    } else {
+        b"0123456789abcdef"
+    };
+    let mut body = vec![];
+    let mut n = magnitude;
+    while n > 0 {
+        body.push(digits[(n % u128::from(radix)) as usize]);
+        n /= u128::from(radix);
+    }
+    if magnitude == 0 && spec.precision != Some(0) {
+        body.push(b'0');
+    }
+    if let Some(precision) = spec.precision {
+        body.resize(body.len().max(precision), b'0');
+    }
+    if spec.alt && radix == 8 && body.last() != Some(&b'0') {
+        body.push(b'0');
+    }
+    body.reverse();
+
+    let prefix: &[u8] = if negative {
+        b"-"
+    } else if spec.alt && radix == 16 && magnitude != 0 {
+        if upper {
+            b"0X"
+        } else {
+            b"0x"
+        }
+    } else if spec.plus {
+        b"+"
+    } else if spec.space {
+        b" "
+    } else {
+        b""
+    };
+    pad(
+        spec,
+        prefix,
+        &body,
+        spec.zero && spec.precision.is_none(),
+        out,
+    );
+}
+
+/// Write `text` cut to the spec's precision and padded to its width.
+fn text(spec: &Spec, text: &[u8], out: &mut Vec<u8>) {
+    let text = &text[..spec.precision.unwrap_or(text.len()).min(text.len())];
+    pad(spec, b"", text, false, out);
+}
+
+fn convert(spec: &Spec, kind: Kind, bytes: &[u8], offset: u128, out: &mut Vec<u8>) {
+    let byte = bytes.first().copied().unwrap_or(0);
+    match kind {
+        Kind::Signed => {
+            let value = NativeEndian::read_int(bytes, bytes.len());
+            integer(spec, value < 0, value.unsigned_abs().into(), 10, false, out);
+        }
+        Kind::Unsigned { radix, upper } => {
+            // Signs are never shown for unsigned conversions.
+            let spec = Spec {
+                plus: false,
+                space: false,
+                ..spec.clone()
+            };
+            let value = NativeEndian::read_uint(bytes, bytes.len());
+            integer(&spec, false, value.into(), radix, upper, out);
+        }
+        Kind::Address { radix } | Kind::EndAddress { radix } => {
+            integer(spec, false, offset, radix, false, out)
+        }
+        Kind::Char => text(spec, &[byte], out),
+        Kind::Str => {
+            let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            text(spec, &bytes[..end], out);
+        }
+        Kind::Printable => text(spec, &[crate::render::PRINTABLE[byte as usize]], out),
+        Kind::Escaped => {
+            let escape = match byte {
+                0 => Some(b'0'),
+                0x07 => Some(b'a'),
+                0x08 => Some(b'b'),
+                0x0c => Some(b'f'),
+                b'\n' => Some(b'n'),
+                b'\r' => Some(b'r'),
+                b'\t' => Some(b't'),
+                0x0b => Some(b'v'),
+                _ => None,
+            };
+            match escape {
+                Some(c) => text(spec, &[b'\\', c], out),
+                None if (0x20..0x7f).contains(&byte) => text(spec, &[byte], out),
+                None => text(spec, &crate::render::OCTAL[byte as usize], out),
+            }
+        }
+        Kind::AsciiName => match byte {
+            0..=0x1f => text(spec, ASCII_NAMES[byte as usize].as_bytes(), out),
+            0x7f => text(spec, b"del", out),
+            0x80..=0xff => text(spec, &crate::render::HEX_LOWER[byte as usize], out),
+            _ => text(spec, &[byte], out),
+        },
+    }
+}
+
+/// Renders blocks of input with `hexdump` format strings.
+#[derive(Clone, Debug)]
+pub struct FormatWriter {
+    strings: Vec<FormatString>,
+    block_size: usize,
+    /// The unit holding `%_A`, shown once after the input.
+    end: Option<Unit>,
+}
+
+impl FormatWriter {
+    pub fn new(mut strings: Vec<FormatString>) -> Result<Self, FormatStringError> {
+        let block_size = strings.iter().map(FormatString::size).max().unwrap_or(0);
+        if block_size == 0 {
+            return Err(FormatStringError::Empty);
+        }
+        let mut end = None;
+        for string in &mut strings {
+            // A string shorter than the block repeats its last unit to fill it, unless
+            // that unit has an iteration count of its own.
+            let size = string.size();
+            if let Some(last) = string.units.last_mut() {
+                let unit_size = last.size();
+                if !last.explicit_reps && unit_size > 0 {
+                    last.reps += (block_size - size) / unit_size;
+                }
+            }
+            // Units from the one showing the end offset on are not shown per block.
+            if let Some(at) = string.units.iter().position(Unit::shows_end) {
+                end = Some(string.units[at].clone());
+                string.units.truncate(at);
+            }
+        }
+        Ok(FormatWriter {
+            strings,
+            block_size,
+            end,
+        })
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn render_unit(
+        &self,
+        unit: &Unit,
+        data: &ChunkData<'_>,
+        position: &mut usize,
+        out: &mut Vec<u8>,
+    ) {
+        let chunk = data.padded();
+        for rep in 0..unit.reps {
+            let start = *position;
+            for (i, piece) in unit.pieces.iter().enumerate() {
+                match piece {
+                    Piece::Text(text) => {
+                        // The last of several iterations drops its final whitespace.
+                        let last = rep + 1 == unit.reps && i + 1 == unit.pieces.len();
+                        let trim = last
+                            && unit.reps > 1
+                            && text
+                                .last()
+                                .is_some_and(|c| c.is_ascii_whitespace() || *c == 0x0b);
+                        out.extend_from_slice(&text[..text.len() - trim as usize]);
+                    }
+                    Piece::Conversion { spec, kind, size } => {
+                        let past_end = *position >= chunk.len();
+                        if past_end || (*size > 0 && *position < data.pad) {
+                            // Past the end of the input, or even partly ahead of it in an
+                            // aligned row, conversions become blanks. Ahead of it, single characters
+                            // keep their column so the row lines up with the next.
+                            let width = match kind {
+                                Kind::Char | Kind::Printable if !past_end => spec.width.max(1),
+                                _ => spec.width,
+                            };
+                            pad(
+                                &Spec {
+                                    width,
+                                    ..Spec::default()
+                                },
+                                b"",
+                                b"",
+                                false,
+                                out,
+                            );
+                        } else {
+                            // A field cut short by the end of the input is zero-filled.
+                            let available = &chunk[*position..chunk.len().min(*position + size)];
+                            let mut bytes = [0u8; 8];
+                            let mut long = vec![];
+                            let bytes = if *size <= bytes.len() {
+                                bytes[..available.len()].copy_from_slice(available);
+                                &bytes[..*size]
+                            } else {
+                                long.extend_from_slice(available);
+                                long.resize(*size, 0);
+                                &long[..]
+                            };
+                            let offset = data.offset + *position as u128;
+                            convert(spec, *kind, bytes, offset, out);
+                        }
+                        *position += size;
+                    }
+                }
+            }
+            *position = start + unit.size();
+        }
+    }
+}
+
+impl LineWriter for FormatWriter {
+    fn print_idx(&self, _data: &ChunkData<'_>, _w: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn print_chunk(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()> {
+        let mut line = Vec::with_capacity(self.block_size * 4);
+        for string in &self.strings {
+            let mut position = 0;
+            for unit in &string.units {
+                self.render_unit(unit, data, &mut position, &mut line);
+            }
+        }
+        w.write_all(&line)
+    }
+
+    fn row_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn print_end(&self, start: u128, end: u128, w: &mut dyn Write) -> io::Result<()> {
+        let unit = match self.end {
+            Some(ref unit) if end > start => unit,
+            _ => return Ok(()),
+        };
+        let mut line = vec![];
+        for piece in &unit.pieces {
+            match piece {
+                Piece::Text(text) => line.extend_from_slice(text),
+                Piece::Conversion {
+                    spec,
+                    kind: kind @ Kind::EndAddress { .. },
+                    ..
+                } => convert(spec, *kind, &[], end, &mut line),
+                Piece::Conversion { .. } => {}
+            }
+        }
+        w.write_all(&line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::print_slice_to;
+
+    fn render(strings: &[&str], data: &[u8]) -> String {
+        let strings = strings.iter().map(|s| parse(s).unwrap()).collect();
+        let writers: Vec<Box<dyn LineWriter>> = vec![Box::new(FormatWriter::new(strings).unwrap())];
+        let mut out = vec![];
+        print_slice_to(&writers, 0, data, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn hexdump_canonical() {
+        let strings = [
+            r#""%08.8_Ax\n""#,
+            r#""%08.8_ax  " 8/1 "%02x " "  " 8/1 "%02x ""#,
+            r#""  |" 16/1 "%_p" "|\n""#,
+        ];
+        assert_eq!(
+            render(&strings, b"Hello, world!\n"),
+            "00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a        |Hello, world!.|\n\
+             0000000e\n"
+        );
+        assert_eq!(render(&strings, b""), "");
+    }
+
+    #[test]
+    fn blocks_padding_and_repetition() {
+        assert_eq!(
+            render(&[r#""%_ad: " 4/1 "%3u" "\n""#], &[10, 20, 30, 40, 50, 60]),
+            "0:  10 20 30 40\n4:  50 60      \n"
+        );
+        // The last unit of a shorter string repeats to fill the block.
+        assert_eq!(
+            render(&[r#"4/1 "%02x""#, r#""  " "%_p""#, r#""\n""#], b"abcd"),
+            "61626364  abcd\n"
+        );
+        assert_eq!(
+            render(
+                &[r#"2 "%-4_c|" "%#o" "%+d\n""#],
+                &[b'\n', 0x80, 0, 0, 0, 0, 0, 0, 0, 0]
+            ),
+            "\\n  |200 |0+0\n"
+        );
+    }
+
+    #[test]
+    fn errors_point_at_column() {
+        let source = r#"16/1 "%02y""#;
+        let err = parse(source).err().unwrap();
+        assert_eq!(err.to_string(), "column 10: bad conversion character `y`");
+        assert_eq!(
+            err.context(source).unwrap(),
+            format!("{}\n         ^", source)
+        );
+
+        let err = parse_file("# comment\n\"%_ad\" \"%q\"\n").err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "line 2, column 10: bad conversion character `q`"
+        );
+        for source in &[
+            r#"4294967296/4294967296 "%s""#,
+            r#"100000/100000 "%s""#,
+            r#""%_ad" 1025/1048576 "%s""#,
+        ] {
+            let err = parse(source).err().unwrap();
+            let column = if source.starts_with('"') { 8 } else { 1 };
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "column {}: the format consumes more than 1 GiB at a time",
+                    column
+                )
+            );
+        }
+        assert!(parse(r#"4/1 "%x %x""#).is_err());
+        assert!(parse(r#""%_az""#).is_err());
+        assert_eq!(
+            FormatWriter::new(vec![parse(r#""\n""#).unwrap()]).err(),
+            Some(FormatStringError::Empty)
+        );
+    }
+}
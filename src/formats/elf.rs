@@ -0,0 +1,747 @@
+use super::{read_at, Fields, FormatError, ReadSeek};
+use std::convert::TryFrom;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct ElfOptions {
+    /// Dump the contents of the named section instead of the headers.
+    #[structopt(long = "section")]
+    pub section: Option<String>,
+
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    pub input: PathBuf,
+}
+
+const MAGIC: &[u8; 4] = b"\x7fELF";
+const SHN_XINDEX: u16 = 0xffff;
+const SHT_NOBITS: u32 = 8;
+/// Refuse header tables larger than this rather than allocating for a corrupt count.
+const MAX_ENTRIES: u64 = 1 << 16;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ident {
+    pub is_64: bool,
+    pub big_endian: bool,
+    pub version: u8,
+    pub os_abi: u8,
+    pub abi_version: u8,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileHeader {
+    pub kind: u16,
+    pub machine: u16,
+    pub version: u32,
+    pub entry: u64,
+    pub phoff: u64,
+    pub shoff: u64,
+    pub flags: u32,
+    pub ehsize: u16,
+    pub phentsize: u16,
+    pub phnum: u32,
+    pub shentsize: u16,
+    pub shnum: u32,
+    pub shstrndx: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProgramHeader {
+    pub kind: u32,
+    pub flags: u32,
+    pub offset: u64,
+    pub vaddr: u64,
+    pub paddr: u64,
+    pub filesz: u64,
+    pub memsz: u64,
+    pub align: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SectionHeader {
+    pub name: String,
+    pub name_offset: u32,
+    pub kind: u32,
+    pub flags: u64,
+    pub addr: u64,
+    pub offset: u64,
+    pub size: u64,
+    pub link: u32,
+    pub info: u32,
+    pub addralign: u64,
+    pub entsize: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ElfFile {
+    pub ident: Ident,
+    pub header: FileHeader,
+    pub program_headers: Vec<ProgramHeader>,
+    pub section_headers: Vec<SectionHeader>,
+}
+
+fn malformed<S: Into<String>>(what: S) -> FormatError {
+    FormatError::Malformed(what.into())
+}
+
+impl ElfFile {
+    pub fn parse(reader: &mut dyn ReadSeek) -> Result<ElfFile, FormatError> {
+        let ident = read_at(reader, 0, 16, "ELF identification").map_err(|e| match e {
+            FormatError::Truncated { .. } => FormatError::BadMagic("ELF"),
+            e => e,
+        })?;
+        if &ident[..4] != MAGIC {
+            return Err(FormatError::BadMagic("ELF"));
+        }
+        let is_64 = match ident[4] {
+            1 => false,
+            2 => true,
+            other => return Err(malformed(format!("ELF class {}", other))),
+        };
+        let big_endian = match ident[5] {
+            1 => false,
+            2 => true,
+            other => return Err(malformed(format!("ELF data encoding {}", other))),
+        };
+        let ident = Ident {
+            is_64,
+            big_endian,
+            version: ident[6],
+            os_abi: ident[7],
+            abi_version: ident[8],
+        };
+
+        let header_size = if is_64 { 64 } else { 52 };
+        let raw = read_at(reader, 0, header_size, "ELF header")?;
+        let f = Fields {
+            data: &raw,
+            big_endian,
+        };
+        let mut header = if is_64 {
+            FileHeader {
+                kind: f.u16(16),
+                machine: f.u16(18),
+                version: f.u32(20),
+                entry: f.u64(24),
+                phoff: f.u64(32),
+                shoff: f.u64(40),
+                flags: f.u32(48),
+                ehsize: f.u16(52),
+                phentsize: f.u16(54),
+                phnum: f.u16(56).into(),
+                shentsize: f.u16(58),
+                shnum: f.u16(60).into(),
+                shstrndx: f.u16(62).into(),
+            }
+        } else {
+            FileHeader {
+                kind: f.u16(16),
+                machine: f.u16(18),
+                version: f.u32(20),
+                entry: f.u32(24).into(),
+                phoff: f.u32(28).into(),
+                shoff: f.u32(32).into(),
+                flags: f.u32(36),
+                ehsize: f.u16(40),
+                phentsize: f.u16(42),
+                phnum: f.u16(44).into(),
+                shentsize: f.u16(46),
+                shnum: f.u16(48).into(),
+                shstrndx: f.u16(50).into(),
+            }
+        };
+
+        let program_headers = parse_program_headers(reader, &ident, &header)?;
+
+        // Files with many sections keep the real counts in section header 0.
+        if header.shoff != 0 && (header.shnum == 0 || header.shstrndx == SHN_XINDEX.into()) {
+            let first = read_section_headers(reader, &ident, &header, 1)?;
+            if let Some(first) = first.first() {
+                if header.shnum == 0 {
+                    header.shnum = u32::try_from(first.size)
+                        .map_err(|_| malformed("extended section count"))?;
+                }
+                if header.shstrndx == SHN_XINDEX.into() {
+                    header.shstrndx = first.link;
+                }
+            }
+        }
+        let mut section_headers = if header.shoff == 0 {
+            vec![]
+        } else {
+            read_section_headers(reader, &ident, &header, header.shnum)?
+        };
+        name_sections(reader, &header, &mut section_headers)?;
+
+        Ok(ElfFile {
+            ident,
+            header,
+            program_headers,
+            section_headers,
+        })
+    }
+
+    pub fn section(&self, name: &str) -> Option<&SectionHeader> {
+        self.section_headers.iter().find(|s| s.name == name)
+    }
+}
+
+fn read_table(
+    reader: &mut dyn ReadSeek,
+    offset: u64,
+    entsize: u16,
+    min_entsize: usize,
+    count: u32,
+    what: &str,
+) -> Result<Vec<u8>, FormatError> {
+    if count == 0 {
+        return Ok(vec![]);
+    }
+    if (entsize as usize) < min_entsize {
+        return Err(malformed(format!(
+            "{} entry size {} (expected at least {})",
+            what, entsize, min_entsize
+        )));
+    }
+    if u64::from(count) > MAX_ENTRIES {
+        return Err(malformed(format!("{} count {}", what, count)));
+    }
+    read_at(
+        reader,
+        offset,
+        entsize as usize * count as usize,
+        &format!("{} table", what),
+    )
+}
+
+fn parse_program_headers(
+    reader: &mut dyn ReadSeek,
+    ident: &Ident,
+    header: &FileHeader,
+) -> Result<Vec<ProgramHeader>, FormatError> {
+    let min = if ident.is_64 { 56 } else { 32 };
+    let table = read_table(
+        reader,
+        header.phoff,
+        header.phentsize,
+        min,
+        header.phnum,
+        "program header",
+    )?;
+    Ok(table
+        .chunks(header.phentsize.max(1) as usize)
+        .map(|data| {
+            let f = Fields {
+                data,
+                big_endian: ident.big_endian,
+            };
+            if ident.is_64 {
+                ProgramHeader {
+                    kind: f.u32(0),
+                    flags: f.u32(4),
+                    offset: f.u64(8),
+                    vaddr: f.u64(16),
+                    paddr: f.u64(24),
+                    filesz: f.u64(32),
+                    memsz: f.u64(40),
+                    align: f.u64(48),
+                }
+            } else {
+                ProgramHeader {
+                    kind: f.u32(0),
+                    offset: f.u32(4).into(),
+                    vaddr: f.u32(8).into(),
+                    paddr: f.u32(12).into(),
+                    filesz: f.u32(16).into(),
+                    memsz: f.u32(20).into(),
+                    flags: f.u32(24),
+                    align: f.u32(28).into(),
+                }
+            }
+        })
+        .collect())
+}
+
+fn read_section_headers(
+    reader: &mut dyn ReadSeek,
+    ident: &Ident,
+    header: &FileHeader,
+    count: u32,
+) -> Result<Vec<SectionHeader>, FormatError> {
+    let min = if ident.is_64 { 64 } else { 40 };
+    let table = read_table(
+        reader,
+        header.shoff,
+        header.shentsize,
+        min,
+        count,
+        "section header",
+    )?;
+    Ok(table
+        .chunks(header.shentsize.max(1) as usize)
+        .map(|data| {
+            let f = Fields {
+                data,
+                big_endian: ident.big_endian,
+            };
+            if ident.is_64 {
+                SectionHeader {
+                    name: String::new(),
+                    name_offset: f.u32(0),
+                    kind: f.u32(4),
+                    flags: f.u64(8),
+                    addr: f.u64(16),
+                    offset: f.u64(24),
+                    size: f.u64(32),
+                    link: f.u32(40),
+                    info: f.u32(44),
+                    addralign: f.u64(48),
+                    entsize: f.u64(56),
+                }
+            } else {
+                SectionHeader {
+                    name: String::new(),
+                    name_offset: f.u32(0),
+                    kind: f.u32(4),
+                    flags: f.u32(8).into(),
+                    addr: f.u32(12).into(),
+                    offset: f.u32(16).into(),
+                    size: f.u32(20).into(),
+                    link: f.u32(24),
+                    info: f.u32(28),
+                    addralign: f.u32(32).into(),
+                    entsize: f.u32(36).into(),
+                }
+            }
+        })
+        .collect())
+}
+
+fn name_sections(
+    reader: &mut dyn ReadSeek,
+    header: &FileHeader,
+    sections: &mut [SectionHeader],
+) -> Result<(), FormatError> {
+    let strtab = match sections.get(header.shstrndx as usize) {
+        Some(s) if header.shstrndx != 0 && s.kind != SHT_NOBITS => s,
+        _ => return Ok(()),
+    };
+    if strtab.size > 16 << 20 {
+        return Err(malformed(format!(
+            "section name table size {:#x}",
+            strtab.size
+        )));
+    }
+    let names = read_at(
+        reader,
+        strtab.offset,
+        strtab.size as usize,
+        "section name table",
+    )?;
+    for section in sections.iter_mut() {
+        let start = section.name_offset as usize;
+        if start >= names.len() {
+            continue;
+        }
+        let end = names[start..]
+            .iter()
+            .position(|&b| b == 0)
+            .map_or(names.len(), |n| start + n);
+        section.name = String::from_utf8_lossy(&names[start..end]).into_owned();
+    }
+    Ok(())
+}
+
+fn file_type_name(kind: u16) -> &'static str {
+    match kind {
+        0 => "NONE",
+        1 => "REL (relocatable)",
+        2 => "EXEC (executable)",
+        3 => "DYN (shared object)",
+        4 => "CORE (core dump)",
+        _ => "unknown",
+    }
+}
+
+fn machine_name(machine: u16) -> &'static str {
+    match machine {
+        0 => "none",
+        2 => "SPARC",
+        3 => "x86",
+        8 => "MIPS",
+        20 => "PowerPC",
+        21 => "PowerPC64",
+        22 => "S/390",
+        40 => "ARM",
+        42 => "SuperH",
+        43 => "SPARC V9",
+        50 => "IA-64",
+        62 => "x86-64",
+        83 => "AVR",
+        183 => "AArch64",
+        243 => "RISC-V",
+        247 => "BPF",
+        258 => "LoongArch",
+        _ => "unknown",
+    }
+}
+
+fn segment_type_name(kind: u32) -> String {
+    match kind {
+        0 => "NULL".into(),
+        1 => "LOAD".into(),
+        2 => "DYNAMIC".into(),
+        3 => "INTERP".into(),
+        4 => "NOTE".into(),
+        5 => "SHLIB".into(),
+        6 => "PHDR".into(),
+        7 => "TLS".into(),
+        0x6474_e550 => "GNU_EH_FRAME".into(),
+        0x6474_e551 => "GNU_STACK".into(),
+        0x6474_e552 => "GNU_RELRO".into(),
+        0x6474_e553 => "GNU_PROPERTY".into(),
+        other => format!("{:#x}", other),
+    }
+}
+
+fn section_type_name(kind: u32) -> String {
+    match kind {
+        0 => "NULL".into(),
+        1 => "PROGBITS".into(),
+        2 => "SYMTAB".into(),
+        3 => "STRTAB".into(),
+        4 => "RELA".into(),
+        5 => "HASH".into(),
+        6 => "DYNAMIC".into(),
+        7 => "NOTE".into(),
+        8 => "NOBITS".into(),
+        9 => "REL".into(),
+        11 => "DYNSYM".into(),
+        14 => "INIT_ARRAY".into(),
+        15 => "FINI_ARRAY".into(),
+        16 => "PREINIT_ARRAY".into(),
+        17 => "GROUP".into(),
+        18 => "SYMTAB_SHNDX".into(),
+        0x6fff_fff6 => "GNU_HASH".into(),
+        0x6fff_fffd => "VERDEF".into(),
+        0x6fff_fffe => "VERNEED".into(),
+        0x6fff_ffff => "VERSYM".into(),
+        other => format!("{:#x}", other),
+    }
+}
+
+fn segment_flags(flags: u32) -> String {
+    let mut out = String::with_capacity(3);
+    out.push(if flags & 4 != 0 { 'R' } else { ' ' });
+    out.push(if flags & 2 != 0 { 'W' } else { ' ' });
+    out.push(if flags & 1 != 0 { 'E' } else { ' ' });
+    out
+}
+
+fn section_flags(flags: u64) -> String {
+    const NAMES: [(u64, char); 8] = [
+        (0x1, 'W'),
+        (0x2, 'A'),
+        (0x4, 'X'),
+        (0x10, 'M'),
+        (0x20, 'S'),
+        (0x40, 'I'),
+        (0x200, 'G'),
+        (0x400, 'T'),
+    ];
+    NAMES
+        .iter()
+        .filter(|(bit, _)| flags & bit != 0)
+        .map(|&(_, ch)| ch)
+        .collect()
+}
+
+/// Print the identification, file header and header tables of `elf`.
+pub fn print_elf(elf: &ElfFile, w: &mut dyn Write) -> io::Result<()> {
+    let ident = &elf.ident;
+    let header = &elf.header;
+    writeln!(
+        w,
+        "ELF{}, {} endian, version {}, OS/ABI {}, ABI version {}",
+        if ident.is_64 { 64 } else { 32 },
+        if ident.big_endian { "big" } else { "little" },
+        ident.version,
+        ident.os_abi,
+        ident.abi_version
+    )?;
+    writeln!(w, "Type:\t\t{}", file_type_name(header.kind))?;
+    writeln!(
+        w,
+        "Machine:\t{} ({})",
+        machine_name(header.machine),
+        header.machine
+    )?;
+    writeln!(w, "Version:\t{}", header.version)?;
+    writeln!(w, "Entry:\t\t{:#x}", header.entry)?;
+    writeln!(w, "Flags:\t\t{:#x}", header.flags)?;
+    writeln!(w, "Header size:\t{}", header.ehsize)?;
+
+    writeln!(w)?;
+    writeln!(
+        w,
+        "Program headers: {} at {:#x}",
+        elf.program_headers.len(),
+        header.phoff
+    )?;
+    if !elf.program_headers.is_empty() {
+        writeln!(
+            w,
+            "{:>4}  {:<14}{:<12}{:<20}{:<20}{:<12}{:<12}{:<5}Align",
+            "#", "Type", "Offset", "VirtAddr", "PhysAddr", "FileSize", "MemSize", "Flg"
+        )?;
+    }
+    for (i, ph) in elf.program_headers.iter().enumerate() {
+        writeln!(
+            w,
+            "{:>4}  {:<14}{:<#12x}{:<#20x}{:<#20x}{:<#12x}{:<#12x}{:<5}{:#x}",
+            i,
+            segment_type_name(ph.kind),
+            ph.offset,
+            ph.vaddr,
+            ph.paddr,
+            ph.filesz,
+            ph.memsz,
+            segment_flags(ph.flags),
+            ph.align
+        )?;
+    }
+
+    writeln!(w)?;
+    writeln!(
+        w,
+        "Section headers: {} at {:#x}",
+        elf.section_headers.len(),
+        header.shoff
+    )?;
+    if !elf.section_headers.is_empty() {
+        writeln!(
+            w,
+            "{:>4}  {:<20}{:<14}{:<20}{:<12}{:<12}{:<6}Align",
+            "#", "Name", "Type", "Address", "Offset", "Size", "Flags"
+        )?;
+    }
+    for (i, sh) in elf.section_headers.iter().enumerate() {
+        writeln!(
+            w,
+            "{:>4}  {:<20}{:<14}{:<#20x}{:<#12x}{:<#12x}{:<6}{}",
+            i,
+            sh.name,
+            section_type_name(sh.kind),
+            sh.addr,
+            sh.offset,
+            sh.size,
+            section_flags(sh.flags),
+            sh.addralign
+        )?;
+    }
+    Ok(())
+}
+
+/// The file range holding the contents of section `name`.
+pub fn section_range(elf: &ElfFile, name: &str) -> Result<(u64, u64), FormatError> {
+    let section = elf
+        .section(name)
+        .ok_or_else(|| FormatError::NoSection(name.to_string()))?;
+    if section.kind == SHT_NOBITS {
+        return Err(FormatError::EmptySection(name.to_string()));
+    }
+    Ok((section.offset, section.size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// A minimal 32-bit big-endian ELF with one program header and three sections.
+    fn tiny_elf32_be() -> Vec<u8> {
+        let mut data = vec![0u8; 0x120];
+        data[..16].copy_from_slice(b"\x7fELF\x02\x02\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00");
+        data[4] = 1; // ELFCLASS32
+        let put16 =
+            |d: &mut Vec<u8>, at: usize, v: u16| d[at..at + 2].copy_from_slice(&v.to_be_bytes());
+        let put32 =
+            |d: &mut Vec<u8>, at: usize, v: u32| d[at..at + 4].copy_from_slice(&v.to_be_bytes());
+        put16(&mut data, 16, 2); // EXEC
+        put16(&mut data, 18, 8); // MIPS
+        put32(&mut data, 20, 1);
+        put32(&mut data, 24, 0x40_0000);
+        put32(&mut data, 28, 52); // phoff
+        put32(&mut data, 32, 0x60); // shoff
+        put16(&mut data, 40, 52);
+        put16(&mut data, 42, 32);
+        put16(&mut data, 44, 1);
+        put16(&mut data, 46, 40);
+        put16(&mut data, 48, 3);
+        put16(&mut data, 50, 2);
+        // PT_LOAD
+        put32(&mut data, 52, 1);
+        put32(&mut data, 56, 0);
+        put32(&mut data, 60, 0x40_0000);
+        put32(&mut data, 68, 0x100);
+        put32(&mut data, 72, 0x100);
+        put32(&mut data, 76, 5);
+        // .text at 0xE0, 8 bytes
+        put32(&mut data, 0x60 + 40, 1);
+        put32(&mut data, 0x60 + 44, 1);
+        put32(&mut data, 0x60 + 48, 6);
+        put32(&mut data, 0x60 + 56, 0xE0);
+        put32(&mut data, 0x60 + 60, 8);
+        // .shstrtab at 0xF0
+        put32(&mut data, 0x60 + 80, 7);
+        put32(&mut data, 0x60 + 84, 3);
+        put32(&mut data, 0x60 + 96, 0xF0);
+        put32(&mut data, 0x60 + 100, 17);
+        data[0xF0..0xF0 + 17].copy_from_slice(b"\0.text\0.shstrtab\0");
+        data.truncate(0xF0 + 17);
+        data
+    }
+
+    /// The same layout as `tiny_elf32_be` as a 64-bit little-endian x86-64 file.
+    fn tiny_elf64_le() -> Vec<u8> {
+        let mut data = vec![0u8; 0x170];
+        data[..16].copy_from_slice(b"\x7fELF\x02\x01\x01\x03\x00\x00\x00\x00\x00\x00\x00\x00");
+        let put16 =
+            |d: &mut Vec<u8>, at: usize, v: u16| d[at..at + 2].copy_from_slice(&v.to_le_bytes());
+        let put32 =
+            |d: &mut Vec<u8>, at: usize, v: u32| d[at..at + 4].copy_from_slice(&v.to_le_bytes());
+        let put64 =
+            |d: &mut Vec<u8>, at: usize, v: u64| d[at..at + 8].copy_from_slice(&v.to_le_bytes());
+        put16(&mut data, 16, 3); // DYN
+        put16(&mut data, 18, 62); // x86-64
+        put32(&mut data, 20, 1);
+        put64(&mut data, 24, 0x5555_5555_4040);
+        put64(&mut data, 32, 64); // phoff
+        put64(&mut data, 40, 0x80); // shoff
+        put16(&mut data, 52, 64);
+        put16(&mut data, 54, 56);
+        put16(&mut data, 56, 1);
+        put16(&mut data, 58, 64);
+        put16(&mut data, 60, 3);
+        put16(&mut data, 62, 2);
+        // PT_LOAD
+        put32(&mut data, 64, 1);
+        put32(&mut data, 68, 5);
+        put64(&mut data, 72, 0x140);
+        put64(&mut data, 80, 0x5555_5555_4140);
+        put64(&mut data, 88, 0x4140);
+        put64(&mut data, 96, 0x30);
+        put64(&mut data, 104, 0x1030);
+        put64(&mut data, 112, 0x1000);
+        // .text at 0x140, 8 bytes
+        put32(&mut data, 0x80 + 64, 1);
+        put32(&mut data, 0x80 + 68, 1);
+        put64(&mut data, 0x80 + 72, 6);
+        put64(&mut data, 0x80 + 80, 0x5555_5555_4140);
+        put64(&mut data, 0x80 + 88, 0x140);
+        put64(&mut data, 0x80 + 96, 8);
+        // .shstrtab at 0x150
+        put32(&mut data, 0x80 + 128, 7);
+        put32(&mut data, 0x80 + 132, 3);
+        put64(&mut data, 0x80 + 152, 0x150);
+        put64(&mut data, 0x80 + 160, 17);
+        data[0x150..0x150 + 17].copy_from_slice(b"\0.text\0.shstrtab\0");
+        data.truncate(0x150 + 17);
+        data
+    }
+
+    #[test]
+    fn parse_elf64_le() {
+        let elf = ElfFile::parse(&mut Cursor::new(tiny_elf64_le())).unwrap();
+        assert!(elf.ident.is_64);
+        assert!(!elf.ident.big_endian);
+        assert_eq!(elf.ident.os_abi, 3);
+        assert_eq!(
+            elf.header,
+            FileHeader {
+                kind: 3,
+                machine: 62,
+                version: 1,
+                entry: 0x5555_5555_4040,
+                phoff: 64,
+                shoff: 0x80,
+                flags: 0,
+                ehsize: 64,
+                phentsize: 56,
+                phnum: 1,
+                shentsize: 64,
+                shnum: 3,
+                shstrndx: 2,
+            }
+        );
+        assert_eq!(
+            elf.program_headers,
+            vec![ProgramHeader {
+                kind: 1,
+                flags: 5,
+                offset: 0x140,
+                vaddr: 0x5555_5555_4140,
+                paddr: 0x4140,
+                filesz: 0x30,
+                memsz: 0x1030,
+                align: 0x1000,
+            }]
+        );
+        let names: Vec<&str> = elf
+            .section_headers
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["", ".text", ".shstrtab"]);
+        assert_eq!(elf.section_headers[1].flags, 6);
+        assert_eq!(elf.section_headers[1].addr, 0x5555_5555_4140);
+        assert_eq!(section_range(&elf, ".text").unwrap(), (0x140, 8));
+    }
+
+    #[test]
+    fn parse_elf32_be() {
+        let elf = ElfFile::parse(&mut Cursor::new(tiny_elf32_be())).unwrap();
+        assert!(!elf.ident.is_64);
+        assert!(elf.ident.big_endian);
+        assert_eq!(elf.header.machine, 8);
+        assert_eq!(elf.header.entry, 0x40_0000);
+        assert_eq!(elf.program_headers.len(), 1);
+        assert_eq!(elf.program_headers[0].filesz, 0x100);
+        assert_eq!(elf.program_headers[0].flags, 5);
+        let names: Vec<&str> = elf
+            .section_headers
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["", ".text", ".shstrtab"]);
+        assert_eq!(section_range(&elf, ".text").unwrap(), (0xE0, 8));
+        assert!(matches!(
+            section_range(&elf, ".data"),
+            Err(FormatError::NoSection(_))
+        ));
+    }
+
+    #[test]
+    fn malformed_headers() {
+        let err = ElfFile::parse(&mut Cursor::new(b"MZ\x90\x00".to_vec())).unwrap_err();
+        assert_eq!(err.to_string(), "not a valid ELF file");
+
+        let mut data = tiny_elf32_be();
+        data.truncate(0x80);
+        let err = ElfFile::parse(&mut Cursor::new(data)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "truncated section header table at offset 0x60"
+        );
+
+        let mut data = tiny_elf32_be();
+        data[43] = 8; // phentsize
+        let err = ElfFile::parse(&mut Cursor::new(data)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "malformed program header entry size 8 (expected at least 32)"
+        );
+    }
+}
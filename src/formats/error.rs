@@ -0,0 +1,20 @@
+use std::io;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum FormatError {
+    #[error("not a valid {0} file")]
+    BadMagic(&'static str),
+    #[error("truncated {what} at offset {offset:#x}")]
+    Truncated { what: String, offset: u64 },
+    #[error("malformed {0}")]
+    Malformed(String),
+    #[error("no section named {0:?}")]
+    NoSection(String),
+    #[error("section {0:?} has no data in the file")]
+    EmptySection(String),
+    #[error("no entry named {0:?}")]
+    NoEntry(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
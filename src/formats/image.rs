@@ -0,0 +1,537 @@
+use super::{Fields, FormatError};
+use crate::{print_lines_to, CanonicalWriter, LineWriter};
+use std::fmt;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct ImageOptions {
+    /// Dump the bytes of each chunk after its summary line.
+    #[structopt(long = "dump")]
+    pub dump: bool,
+
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    pub input: PathBuf,
+}
+
+const PNG_SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+const JPEG_SOI: &[u8; 2] = b"\xff\xd8";
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ImageKind {
+    Png,
+    Jpeg,
+    Gif,
+}
+
+impl fmt::Display for ImageKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ImageKind::Png => "PNG",
+            ImageKind::Jpeg => "JPEG",
+            ImageKind::Gif => "GIF",
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Status {
+    /// Structurally sound, with no checksum to verify.
+    Ok,
+    CrcOk,
+    CrcMismatch {
+        stored: u32,
+        computed: u32,
+    },
+    /// The chunk claims `expected` bytes but the file ends first.
+    Truncated {
+        expected: u64,
+    },
+    /// The bytes at this offset are not a chunk at all; walking stops here.
+    Invalid(String),
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Status::Ok => f.write_str("ok"),
+            Status::CrcOk => f.write_str("CRC ok"),
+            Status::CrcMismatch { stored, computed } => write!(
+                f,
+                "CRC MISMATCH (stored {:#010x}, computed {:#010x})",
+                stored, computed
+            ),
+            Status::Truncated { expected } => {
+                write!(f, "TRUNCATED (expected {} bytes)", expected)
+            }
+            Status::Invalid(why) => write!(f, "INVALID ({})", why),
+        }
+    }
+}
+
+/// One chunk, marker segment or block, covering `len` bytes of the file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chunk {
+    pub offset: u64,
+    pub len: u64,
+    pub name: String,
+    pub status: Status,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub kind: ImageKind,
+    pub chunks: Vec<Chunk>,
+}
+
+impl Image {
+    /// Walk every chunk of a PNG, JPEG or GIF image. Damage inside the image is
+    /// recorded in the chunks' status; only an unrecognised signature is an error.
+    pub fn parse(data: &[u8]) -> Result<Image, FormatError> {
+        let mut walker = Walker {
+            data,
+            pos: 0,
+            chunks: vec![],
+        };
+        let kind = if data.starts_with(PNG_SIGNATURE) {
+            walker.png();
+            ImageKind::Png
+        } else if data.starts_with(JPEG_SOI) {
+            walker.jpeg();
+            ImageKind::Jpeg
+        } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            walker.gif();
+            ImageKind::Gif
+        } else {
+            return Err(FormatError::BadMagic("PNG, JPEG or GIF"));
+        };
+        walker.trailing();
+        Ok(Image {
+            kind,
+            chunks: walker.chunks,
+        })
+    }
+}
+
+struct Walker<'a> {
+    data: &'a [u8],
+    pos: usize,
+    chunks: Vec<Chunk>,
+}
+
+impl<'a> Walker<'a> {
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    fn be(&self) -> Fields<'a> {
+        Fields {
+            data: &self.data[self.pos..],
+            big_endian: true,
+        }
+    }
+
+    /// Record a chunk of `len` bytes at the current position and move past it.
+    /// Returns false, after recording what is left, if the file ends first.
+    fn push(&mut self, name: String, len: usize, status: Status) -> bool {
+        let available = self.remaining();
+        let (len, status) = if len > available {
+            (
+                available,
+                Status::Truncated {
+                    expected: len as u64,
+                },
+            )
+        } else {
+            (len, status)
+        };
+        let truncated = matches!(status, Status::Truncated { .. });
+        self.chunks.push(Chunk {
+            offset: self.pos as u64,
+            len: len as u64,
+            name,
+            status,
+        });
+        self.pos += len;
+        !truncated
+    }
+
+    fn invalid(&mut self, why: String) {
+        let len = self.remaining() as u64;
+        self.chunks.push(Chunk {
+            offset: self.pos as u64,
+            len,
+            name: "?".to_string(),
+            status: Status::Invalid(why),
+        });
+        self.pos = self.data.len();
+    }
+
+    /// Anything after the final chunk.
+    fn trailing(&mut self) {
+        if self.remaining() > 0 {
+            self.push("trailing data".to_string(), self.remaining(), Status::Ok);
+        }
+    }
+
+    fn png(&mut self) {
+        self.push("signature".to_string(), PNG_SIGNATURE.len(), Status::Ok);
+        while self.remaining() > 0 {
+            if self.remaining() < 8 {
+                self.push("chunk header".to_string(), 8, Status::Ok);
+                return;
+            }
+            let len = self.be().u32(0) as usize;
+            let kind = &self.data[self.pos + 4..self.pos + 8];
+            let name = String::from_utf8_lossy(kind).into_owned();
+            let total = len.saturating_add(12);
+            let status = if total <= self.remaining() {
+                let body = &self.data[self.pos + 4..self.pos + 8 + len];
+                let stored = self.be().u32(8 + len);
+                let computed = crc32fast::hash(body);
+                if stored == computed {
+                    Status::CrcOk
+                } else {
+                    Status::CrcMismatch { stored, computed }
+                }
+            } else {
+                Status::Ok
+            };
+            let end = kind == b"IEND";
+            if !self.push(name, total, status) || end {
+                return;
+            }
+        }
+    }
+
+    fn jpeg(&mut self) {
+        self.push("SOI".to_string(), 2, Status::Ok);
+        while self.remaining() > 0 {
+            let start = self.pos;
+            if self.data[start] != 0xff {
+                self.invalid(format!("expected marker, found {:#04x}", self.data[start]));
+                return;
+            }
+            // Any number of 0xFF fill bytes may precede the marker code.
+            let mut at = start;
+            while at < self.data.len() && self.data[at] == 0xff {
+                at += 1;
+            }
+            let marker = match self.data.get(at) {
+                Some(&marker) => marker,
+                None => {
+                    self.push("marker".to_string(), self.remaining() + 1, Status::Ok);
+                    return;
+                }
+            };
+            let prefix = at + 1 - start;
+            let name = jpeg_marker_name(marker);
+            match marker {
+                0x01 | 0xd0..=0xd8 => {
+                    self.push(name, prefix, Status::Ok);
+                }
+                0xd9 => {
+                    self.push(name, prefix, Status::Ok);
+                    return;
+                }
+                _ => {
+                    if self.remaining() < prefix + 2 {
+                        self.push(name, prefix + 2, Status::Ok);
+                        return;
+                    }
+                    let len = Fields {
+                        data: &self.data[at + 1..],
+                        big_endian: true,
+                    }
+                    .u16(0) as usize;
+                    if len < 2 {
+                        self.invalid(format!("{} segment length {}", name, len));
+                        return;
+                    }
+                    if !self.push(name, prefix + len, Status::Ok) {
+                        return;
+                    }
+                    if marker == 0xda {
+                        self.scan_data();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Entropy-coded data runs until a marker other than a stuffed zero or a restart.
+    fn scan_data(&mut self) {
+        let data = self.data;
+        let mut at = self.pos;
+        while at + 1 < data.len() {
+            if data[at] == 0xff && !matches!(data[at + 1], 0x00 | 0xd0..=0xd7 | 0xff) {
+                break;
+            }
+            at += 1;
+        }
+        if at + 1 >= data.len() {
+            at = data.len();
+        }
+        if at > self.pos {
+            self.push("scan data".to_string(), at - self.pos, Status::Ok);
+        }
+    }
+
+    /// Length of a run of GIF sub-blocks starting at `at`, including the terminator.
+    fn gif_sub_blocks(&self, mut at: usize) -> usize {
+        let start = at;
+        loop {
+            match self.data.get(at) {
+                Some(0) => return at + 1 - start,
+                Some(&len) => at += 1 + len as usize,
+                // Past the end: the caller reports the run as truncated.
+                None => return at + 1 - start,
+            }
+        }
+    }
+
+    fn gif(&mut self) {
+        if !self.push("header".to_string(), 6, Status::Ok)
+            || !self.push("logical screen".to_string(), 7, Status::Ok)
+        {
+            return;
+        }
+        let packed = self.data[self.pos - 3];
+        if packed & 0x80 != 0 {
+            let len = 3 << ((packed & 0x07) + 1);
+            if !self.push("global color table".to_string(), len, Status::Ok) {
+                return;
+            }
+        }
+        while self.remaining() > 0 {
+            let introducer = self.data[self.pos];
+            match introducer {
+                0x21 => {
+                    let label = match self.data.get(self.pos + 1) {
+                        Some(&label) => label,
+                        None => {
+                            self.push("extension".to_string(), 2, Status::Ok);
+                            return;
+                        }
+                    };
+                    let len = 2 + self.gif_sub_blocks(self.pos + 2);
+                    if !self.push(gif_extension_name(label), len, Status::Ok) {
+                        return;
+                    }
+                }
+                0x2c => {
+                    if self.remaining() < 10 {
+                        self.push("image descriptor".to_string(), 10, Status::Ok);
+                        return;
+                    }
+                    let packed = self.data[self.pos + 9];
+                    if !self.push("image descriptor".to_string(), 10, Status::Ok) {
+                        return;
+                    }
+                    if packed & 0x80 != 0 {
+                        let len = 3 << ((packed & 0x07) + 1);
+                        if !self.push("local color table".to_string(), len, Status::Ok) {
+                            return;
+                        }
+                    }
+                    let len = 1 + self.gif_sub_blocks(self.pos + 1);
+                    if !self.push("image data".to_string(), len, Status::Ok) {
+                        return;
+                    }
+                }
+                0x3b => {
+                    self.push("trailer".to_string(), 1, Status::Ok);
+                    return;
+                }
+                other => {
+                    self.invalid(format!("unknown block introducer {:#04x}", other));
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn jpeg_marker_name(marker: u8) -> String {
+    let name = match marker {
+        0x01 => "TEM",
+        0xc4 => "DHT",
+        0xc8 => "JPG",
+        0xcc => "DAC",
+        0xc0..=0xcf => return format!("SOF{}", marker - 0xc0),
+        0xd0..=0xd7 => return format!("RST{}", marker - 0xd0),
+        0xd8 => "SOI",
+        0xd9 => "EOI",
+        0xda => "SOS",
+        0xdb => "DQT",
+        0xdc => "DNL",
+        0xdd => "DRI",
+        0xe0..=0xef => return format!("APP{}", marker - 0xe0),
+        0xfe => "COM",
+        _ => return format!("marker {:#04x}", marker),
+    };
+    name.to_string()
+}
+
+fn gif_extension_name(label: u8) -> String {
+    match label {
+        0x01 => "plain text extension".to_string(),
+        0xf9 => "graphic control extension".to_string(),
+        0xfe => "comment extension".to_string(),
+        0xff => "application extension".to_string(),
+        _ => format!("extension {:#04x}", label),
+    }
+}
+
+/// Print one line per chunk of `image`, optionally followed by a canonical dump
+/// of the chunk's bytes taken from `data`.
+pub fn print_image(
+    image: &Image,
+    data: &[u8],
+    opt: &ImageOptions,
+    w: &mut dyn Write,
+) -> io::Result<()> {
+    writeln!(w, "{} image: {} chunks", image.kind, image.chunks.len())?;
+    let writers: Vec<Box<dyn LineWriter>> = vec![Box::new(CanonicalWriter::default())];
+    for chunk in &image.chunks {
+        writeln!(
+            w,
+            "{:#010x}\t{:>8}\t{:<26}\t{}",
+            chunk.offset, chunk.len, chunk.name, chunk.status
+        )?;
+        if opt.dump {
+            let start = chunk.offset as usize;
+            let bytes = &data[start..start + chunk.len as usize];
+            print_lines_to(&writers, chunk.offset.into(), &mut &bytes[..], w)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png_chunk(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut chunk = (body.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(kind);
+        chunk.extend_from_slice(body);
+        let mut crc = kind.to_vec();
+        crc.extend_from_slice(body);
+        chunk.extend_from_slice(&crc32fast::hash(&crc).to_be_bytes());
+        chunk
+    }
+
+    fn summary(image: &Image) -> Vec<(u64, u64, &str, &Status)> {
+        image
+            .chunks
+            .iter()
+            .map(|c| (c.offset, c.len, c.name.as_str(), &c.status))
+            .collect()
+    }
+
+    #[test]
+    fn png_chunks() {
+        let mut data = PNG_SIGNATURE.to_vec();
+        data.extend(png_chunk(b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]));
+        let mut bad = png_chunk(b"tEXt", b"k\0v");
+        bad[9] ^= 0xff;
+        data.extend(bad);
+        data.extend(png_chunk(b"IDAT", &[0u8; 40]));
+        data.truncate(data.len() - 10);
+
+        let image = Image::parse(&data).unwrap();
+        assert_eq!(image.kind, ImageKind::Png);
+        assert_eq!(
+            summary(&image),
+            vec![
+                (0x00, 8, "signature", &Status::Ok),
+                (0x08, 25, "IHDR", &Status::CrcOk),
+                (
+                    0x21,
+                    15,
+                    "tEXt",
+                    &Status::CrcMismatch {
+                        stored: crc32fast::hash(b"tEXtk\0v"),
+                        computed: crc32fast::hash(b"tEXtk\xffv"),
+                    }
+                ),
+                (0x30, 42, "IDAT", &Status::Truncated { expected: 52 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn jpeg_markers() {
+        let mut data = vec![0xff, 0xd8];
+        data.extend_from_slice(&[0xff, 0xe0, 0x00, 0x04, b'J', b'F']);
+        data.extend_from_slice(&[0xff, 0xda, 0x00, 0x02]);
+        data.extend_from_slice(&[0x12, 0xff, 0x00, 0x34, 0xff, 0xd0, 0x56]);
+        data.extend_from_slice(&[0xff, 0xd9, 0xaa]);
+
+        let image = Image::parse(&data).unwrap();
+        assert_eq!(
+            summary(&image),
+            vec![
+                (0x00, 2, "SOI", &Status::Ok),
+                (0x02, 6, "APP0", &Status::Ok),
+                (0x08, 4, "SOS", &Status::Ok),
+                (0x0c, 7, "scan data", &Status::Ok),
+                (0x13, 2, "EOI", &Status::Ok),
+                (0x15, 1, "trailing data", &Status::Ok),
+            ]
+        );
+    }
+
+    #[test]
+    fn dump_follows_each_chunk() {
+        let data = b"\xff\xd8\xff\xd9";
+        let image = Image::parse(data).unwrap();
+        let opt = ImageOptions {
+            dump: true,
+            input: PathBuf::new(),
+        };
+        let mut out = vec![];
+        print_image(&image, data, &opt, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "JPEG image: 2 chunks\n\
+             0x00000000\t       2\tSOI                       \tok\n\
+             0x00000000\tFF D8                                           \t|..|\n\
+             0x00000002\t       2\tEOI                       \tok\n\
+             0x00000002\tFF D9                                           \t|..|\n"
+        );
+    }
+
+    #[test]
+    fn gif_blocks() {
+        let mut data = b"GIF89a".to_vec();
+        data.extend_from_slice(&[1, 0, 1, 0, 0x80, 0, 0]);
+        data.extend_from_slice(&[0, 0, 0, 0xff, 0xff, 0xff]);
+        data.extend_from_slice(&[0x21, 0xf9, 4, 0, 0, 0, 0, 0]);
+        data.extend_from_slice(&[0x2c, 0, 0, 0, 0, 1, 0, 1, 0, 0]);
+        data.extend_from_slice(&[2, 2, 0x4c, 0x01, 0]);
+        data.push(0x3b);
+
+        let image = Image::parse(&data).unwrap();
+        let names: Vec<&str> = image.chunks.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "header",
+                "logical screen",
+                "global color table",
+                "graphic control extension",
+                "image descriptor",
+                "image data",
+                "trailer"
+            ]
+        );
+        assert_eq!(image.chunks[5].len, 5);
+
+        let err = Image::parse(b"BM\0\0").unwrap_err();
+        assert_eq!(err.to_string(), "not a valid PNG, JPEG or GIF file");
+    }
+}
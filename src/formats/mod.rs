@@ -0,0 +1,112 @@
+//! Decoders for common binary file formats.
+
+pub mod elf;
+mod error;
+pub mod image;
+pub mod pe;
+pub mod zip;
+
+pub use error::*;
+
+use crate::{print_lines_to, CanonicalWriter, LineWriter};
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+pub trait ReadSeek: Read + Seek {}
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Read exactly `len` bytes at `offset`, describing a short read as a truncated `what`.
+pub(crate) fn read_at(
+    reader: &mut dyn ReadSeek,
+    offset: u64,
+    len: usize,
+    what: &str,
+) -> Result<Vec<u8>, FormatError> {
+    let truncated = || FormatError::Truncated {
+        what: what.to_string(),
+        offset,
+    };
+    let size = reader.seek(SeekFrom::End(0))?;
+    if offset.checked_add(len as u64).is_none_or(|end| end > size) {
+        return Err(truncated());
+    }
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => truncated(),
+        _ => e.into(),
+    })?;
+    Ok(buf)
+}
+
+/// Fixed-width integers at known offsets of an already length-checked buffer.
+#[derive(Copy, Clone)]
+pub(crate) struct Fields<'a> {
+    pub data: &'a [u8],
+    pub big_endian: bool,
+}
+
+impl<'a> Fields<'a> {
+    pub fn u16(&self, at: usize) -> u16 {
+        let bytes = &self.data[at..at + 2];
+        if self.big_endian {
+            BigEndian::read_u16(bytes)
+        } else {
+            LittleEndian::read_u16(bytes)
+        }
+    }
+
+    pub fn u32(&self, at: usize) -> u32 {
+        let bytes = &self.data[at..at + 4];
+        if self.big_endian {
+            BigEndian::read_u32(bytes)
+        } else {
+            LittleEndian::read_u32(bytes)
+        }
+    }
+
+    pub fn u64(&self, at: usize) -> u64 {
+        let bytes = &self.data[at..at + 8];
+        if self.big_endian {
+            BigEndian::read_u64(bytes)
+        } else {
+            LittleEndian::read_u64(bytes)
+        }
+    }
+}
+
+/// Parse an address given in hex with a `0x` prefix, or in decimal.
+pub fn parse_address(s: &str) -> Result<u64, std::num::ParseIntError> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+}
+
+/// Write `len` bytes starting at `offset` to `w` in canonical form.
+pub fn dump_range(
+    reader: &mut dyn ReadSeek,
+    offset: u64,
+    len: u64,
+    w: &mut dyn Write,
+) -> io::Result<()> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let writers: Vec<Box<dyn LineWriter>> = vec![Box::new(CanonicalWriter::default())];
+    print_lines_to(&writers, offset.into(), &mut reader.take(len), w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dump_range_writes_to_the_writer() {
+        let mut reader = io::Cursor::new(b"0123456789abcdefghij".to_vec());
+        let mut out = vec![];
+        dump_range(&mut reader, 0x0e, 4, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "0x0000000e\t65 66 67 68                                     \t|efgh|\n"
+        );
+    }
+}
@@ -0,0 +1,538 @@
+use super::{parse_address, read_at, Fields, FormatError, ReadSeek};
+use crate::byte_unit::parse_bytes;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct PeOptions {
+    /// Dump the contents of the named section instead of the headers.
+    #[structopt(long = "section", conflicts_with = "rva")]
+    pub section: Option<String>,
+    /// Dump the bytes mapped at this relative virtual address.
+    #[structopt(long = "rva", parse(try_from_str = parse_address))]
+    pub rva: Option<u64>,
+    /// Interpret only `length` bytes of the section or RVA.
+    #[structopt(short = "n", long = "length", parse(try_from_str = parse_bytes))]
+    pub length: Option<u128>,
+
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    pub input: PathBuf,
+}
+
+const DOS_MAGIC: &[u8; 2] = b"MZ";
+const PE_SIGNATURE: &[u8; 4] = b"PE\0\0";
+const PE32_MAGIC: u16 = 0x10b;
+const PE32_PLUS_MAGIC: u16 = 0x20b;
+const SECTION_HEADER_SIZE: usize = 40;
+/// The COFF header allows more, but the loader rejects anything above this.
+const MAX_SECTIONS: u16 = 96;
+
+const DATA_DIRECTORY_NAMES: [&str; 16] = [
+    "Export",
+    "Import",
+    "Resource",
+    "Exception",
+    "Certificate",
+    "Base relocation",
+    "Debug",
+    "Architecture",
+    "Global pointer",
+    "TLS",
+    "Load config",
+    "Bound import",
+    "IAT",
+    "Delay import",
+    "CLR runtime",
+    "Reserved",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoffHeader {
+    pub machine: u16,
+    pub number_of_sections: u16,
+    pub time_date_stamp: u32,
+    pub pointer_to_symbol_table: u32,
+    pub number_of_symbols: u32,
+    pub size_of_optional_header: u16,
+    pub characteristics: u16,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OptionalHeader {
+    pub is_pe32_plus: bool,
+    pub linker_version: (u8, u8),
+    pub size_of_code: u32,
+    pub address_of_entry_point: u32,
+    pub base_of_code: u32,
+    pub image_base: u64,
+    pub section_alignment: u32,
+    pub file_alignment: u32,
+    pub size_of_image: u32,
+    pub size_of_headers: u32,
+    pub checksum: u32,
+    pub subsystem: u16,
+    pub dll_characteristics: u16,
+    pub data_directories: Vec<DataDirectory>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataDirectory {
+    pub rva: u32,
+    pub size: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Section {
+    pub name: String,
+    pub virtual_size: u32,
+    pub virtual_address: u32,
+    pub size_of_raw_data: u32,
+    pub pointer_to_raw_data: u32,
+    pub characteristics: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PeFile {
+    /// Offset of the PE signature, from the DOS header's `e_lfanew`.
+    pub pe_offset: u32,
+    pub coff: CoffHeader,
+    pub optional: Option<OptionalHeader>,
+    pub sections: Vec<Section>,
+}
+
+fn malformed<S: Into<String>>(what: S) -> FormatError {
+    FormatError::Malformed(what.into())
+}
+
+impl PeFile {
+    pub fn parse(reader: &mut dyn ReadSeek) -> Result<PeFile, FormatError> {
+        let dos = read_at(reader, 0, 64, "DOS header").map_err(|e| match e {
+            FormatError::Truncated { .. } => FormatError::BadMagic("PE"),
+            e => e,
+        })?;
+        if &dos[..2] != DOS_MAGIC {
+            return Err(FormatError::BadMagic("PE"));
+        }
+        let pe_offset = Fields {
+            data: &dos,
+            big_endian: false,
+        }
+        .u32(0x3C);
+        let signature = read_at(reader, pe_offset.into(), 4, "PE signature")?;
+        if &signature[..] != PE_SIGNATURE {
+            return Err(malformed(format!("PE signature at {:#x}", pe_offset)));
+        }
+
+        let coff_offset = u64::from(pe_offset) + 4;
+        let raw = read_at(reader, coff_offset, 20, "COFF header")?;
+        let f = Fields {
+            data: &raw,
+            big_endian: false,
+        };
+        let coff = CoffHeader {
+            machine: f.u16(0),
+            number_of_sections: f.u16(2),
+            time_date_stamp: f.u32(4),
+            pointer_to_symbol_table: f.u32(8),
+            number_of_symbols: f.u32(12),
+            size_of_optional_header: f.u16(16),
+            characteristics: f.u16(18),
+        };
+
+        let optional_offset = coff_offset + 20;
+        let optional = if coff.size_of_optional_header == 0 {
+            None
+        } else {
+            let raw = read_at(
+                reader,
+                optional_offset,
+                coff.size_of_optional_header.into(),
+                "optional header",
+            )?;
+            Some(parse_optional_header(&raw)?)
+        };
+
+        if coff.number_of_sections > MAX_SECTIONS {
+            return Err(malformed(format!(
+                "section count {}",
+                coff.number_of_sections
+            )));
+        }
+        let table_offset = optional_offset + u64::from(coff.size_of_optional_header);
+        let table = read_at(
+            reader,
+            table_offset,
+            SECTION_HEADER_SIZE * coff.number_of_sections as usize,
+            "section table",
+        )?;
+        let sections = table
+            .chunks(SECTION_HEADER_SIZE)
+            .map(|data| {
+                let f = Fields {
+                    data,
+                    big_endian: false,
+                };
+                let name_end = data[..8].iter().position(|&b| b == 0).unwrap_or(8);
+                Section {
+                    name: String::from_utf8_lossy(&data[..name_end]).into_owned(),
+                    virtual_size: f.u32(8),
+                    virtual_address: f.u32(12),
+                    size_of_raw_data: f.u32(16),
+                    pointer_to_raw_data: f.u32(20),
+                    characteristics: f.u32(36),
+                }
+            })
+            .collect();
+
+        Ok(PeFile {
+            pe_offset,
+            coff,
+            optional,
+            sections,
+        })
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+
+    /// Map a relative virtual address to a file offset, along with the number of
+    /// file-backed bytes from there to the end of its section.
+    pub fn rva_to_offset(&self, rva: u64) -> Option<(u64, u64)> {
+        for section in &self.sections {
+            let start = u64::from(section.virtual_address);
+            let span = section.virtual_size.max(section.size_of_raw_data);
+            if rva < start || rva >= start + u64::from(span) {
+                continue;
+            }
+            let delta = rva - start;
+            let raw = u64::from(section.size_of_raw_data);
+            if delta >= raw {
+                return None;
+            }
+            return Some((u64::from(section.pointer_to_raw_data) + delta, raw - delta));
+        }
+        // Headers are mapped at the start of the image as-is.
+        let headers = u64::from(self.optional.as_ref()?.size_of_headers);
+        if rva < headers {
+            Some((rva, headers - rva))
+        } else {
+            None
+        }
+    }
+}
+
+fn parse_optional_header(raw: &[u8]) -> Result<OptionalHeader, FormatError> {
+    if raw.len() < 2 {
+        return Err(malformed("optional header size"));
+    }
+    let f = Fields {
+        data: raw,
+        big_endian: false,
+    };
+    let is_pe32_plus = match f.u16(0) {
+        PE32_MAGIC => false,
+        PE32_PLUS_MAGIC => true,
+        other => return Err(malformed(format!("optional header magic {:#x}", other))),
+    };
+    let directories_at = if is_pe32_plus { 112 } else { 96 };
+    if raw.len() < directories_at {
+        return Err(malformed(format!(
+            "optional header size {} (expected at least {})",
+            raw.len(),
+            directories_at
+        )));
+    }
+    let count = f.u32(directories_at - 4) as usize;
+    let available = (raw.len() - directories_at) / 8;
+    if count > available {
+        return Err(malformed(format!(
+            "data directory count {} (room for {})",
+            count, available
+        )));
+    }
+    let data_directories = (0..count)
+        .map(|i| DataDirectory {
+            rva: f.u32(directories_at + i * 8),
+            size: f.u32(directories_at + i * 8 + 4),
+        })
+        .collect();
+
+    Ok(OptionalHeader {
+        is_pe32_plus,
+        linker_version: (raw[2], raw[3]),
+        size_of_code: f.u32(4),
+        address_of_entry_point: f.u32(16),
+        base_of_code: f.u32(20),
+        image_base: if is_pe32_plus {
+            f.u64(24)
+        } else {
+            f.u32(28).into()
+        },
+        section_alignment: f.u32(32),
+        file_alignment: f.u32(36),
+        size_of_image: f.u32(56),
+        size_of_headers: f.u32(60),
+        checksum: f.u32(64),
+        subsystem: f.u16(68),
+        dll_characteristics: f.u16(70),
+        data_directories,
+    })
+}
+
+fn machine_name(machine: u16) -> &'static str {
+    match machine {
+        0x0 => "unknown",
+        0x14c => "i386",
+        0x166 => "MIPS",
+        0x1c0 => "ARM",
+        0x1c4 => "ARMv7 Thumb-2",
+        0x200 => "IA-64",
+        0x5064 => "RISC-V 64",
+        0x8664 => "x86-64",
+        0xaa64 => "ARM64",
+        _ => "other",
+    }
+}
+
+fn subsystem_name(subsystem: u16) -> &'static str {
+    match subsystem {
+        1 => "native",
+        2 => "Windows GUI",
+        3 => "Windows console",
+        7 => "POSIX console",
+        9 => "Windows CE GUI",
+        10 => "EFI application",
+        11 => "EFI boot service driver",
+        12 => "EFI runtime driver",
+        13 => "EFI ROM",
+        14 => "Xbox",
+        16 => "Windows boot application",
+        _ => "unknown",
+    }
+}
+
+fn section_flags(characteristics: u32) -> String {
+    const NAMES: [(u32, char); 6] = [
+        (0x20, 'C'),
+        (0x40, 'I'),
+        (0x80, 'U'),
+        (0x2000_0000, 'X'),
+        (0x4000_0000, 'R'),
+        (0x8000_0000, 'W'),
+    ];
+    NAMES
+        .iter()
+        .filter(|(bit, _)| characteristics & bit != 0)
+        .map(|&(_, ch)| ch)
+        .collect()
+}
+
+/// Print the headers, data directories and section table of `pe`.
+pub fn print_pe(pe: &PeFile, w: &mut dyn Write) -> io::Result<()> {
+    let coff = &pe.coff;
+    writeln!(w, "DOS header:\t\te_lfanew = {:#x}", pe.pe_offset)?;
+    writeln!(w, "PE signature:\t\t{:#010x}", pe.pe_offset)?;
+    writeln!(w)?;
+    writeln!(w, "COFF header:\t\t{:#010x}", u64::from(pe.pe_offset) + 4)?;
+    writeln!(
+        w,
+        "Machine:\t\t{} ({:#x})",
+        machine_name(coff.machine),
+        coff.machine
+    )?;
+    writeln!(w, "Sections:\t\t{}", coff.number_of_sections)?;
+    writeln!(w, "Timestamp:\t\t{:#x}", coff.time_date_stamp)?;
+    writeln!(
+        w,
+        "Symbol table:\t\t{:#x} ({} symbols)",
+        coff.pointer_to_symbol_table, coff.number_of_symbols
+    )?;
+    writeln!(w, "Optional header size:\t{}", coff.size_of_optional_header)?;
+    writeln!(w, "Characteristics:\t{:#06x}", coff.characteristics)?;
+
+    if let Some(ref opt) = pe.optional {
+        writeln!(w)?;
+        writeln!(
+            w,
+            "Optional header:\t{:#010x} ({})",
+            u64::from(pe.pe_offset) + 24,
+            if opt.is_pe32_plus { "PE32+" } else { "PE32" }
+        )?;
+        writeln!(
+            w,
+            "Linker version:\t\t{}.{}",
+            opt.linker_version.0, opt.linker_version.1
+        )?;
+        writeln!(w, "Entry point:\t\t{:#x}", opt.address_of_entry_point)?;
+        writeln!(w, "Base of code:\t\t{:#x}", opt.base_of_code)?;
+        writeln!(w, "Image base:\t\t{:#x}", opt.image_base)?;
+        writeln!(w, "Section alignment:\t{:#x}", opt.section_alignment)?;
+        writeln!(w, "File alignment:\t\t{:#x}", opt.file_alignment)?;
+        writeln!(w, "Size of code:\t\t{:#x}", opt.size_of_code)?;
+        writeln!(w, "Size of image:\t\t{:#x}", opt.size_of_image)?;
+        writeln!(w, "Size of headers:\t{:#x}", opt.size_of_headers)?;
+        writeln!(w, "Checksum:\t\t{:#x}", opt.checksum)?;
+        writeln!(
+            w,
+            "Subsystem:\t\t{} ({})",
+            subsystem_name(opt.subsystem),
+            opt.subsystem
+        )?;
+        writeln!(w, "DLL characteristics:\t{:#06x}", opt.dll_characteristics)?;
+
+        writeln!(w)?;
+        writeln!(w, "Data directories: {}", opt.data_directories.len())?;
+        writeln!(
+            w,
+            "{:>4}  {:<18}{:<12}{:<12}Offset",
+            "#", "Name", "RVA", "Size"
+        )?;
+        for (i, dir) in opt.data_directories.iter().enumerate() {
+            if dir.rva == 0 && dir.size == 0 {
+                continue;
+            }
+            let offset = match pe.rva_to_offset(dir.rva.into()) {
+                Some((offset, _)) => format!("{:#x}", offset),
+                None => "-".to_string(),
+            };
+            writeln!(
+                w,
+                "{:>4}  {:<18}{:<#12x}{:<#12x}{}",
+                i,
+                DATA_DIRECTORY_NAMES.get(i).unwrap_or(&"?"),
+                dir.rva,
+                dir.size,
+                offset
+            )?;
+        }
+    }
+
+    writeln!(w)?;
+    writeln!(w, "Sections: {}", pe.sections.len())?;
+    writeln!(
+        w,
+        "{:>4}  {:<10}{:<12}{:<12}{:<12}{:<12}Flags",
+        "#", "Name", "VirtAddr", "VirtSize", "RawOffset", "RawSize"
+    )?;
+    for (i, section) in pe.sections.iter().enumerate() {
+        writeln!(
+            w,
+            "{:>4}  {:<10}{:<#12x}{:<#12x}{:<#12x}{:<#12x}{}",
+            i,
+            section.name,
+            section.virtual_address,
+            section.virtual_size,
+            section.pointer_to_raw_data,
+            section.size_of_raw_data,
+            section_flags(section.characteristics)
+        )?;
+    }
+    Ok(())
+}
+
+/// The file range selected by `--section` or `--rva`, if either was given.
+pub fn selected_range(pe: &PeFile, opt: &PeOptions) -> Result<Option<(u64, u64)>, FormatError> {
+    let (offset, available) = if let Some(ref name) = opt.section {
+        let section = pe
+            .section(name)
+            .ok_or_else(|| FormatError::NoSection(name.clone()))?;
+        if section.size_of_raw_data == 0 {
+            return Err(FormatError::EmptySection(name.clone()));
+        }
+        (
+            section.pointer_to_raw_data.into(),
+            section.size_of_raw_data.into(),
+        )
+    } else if let Some(rva) = opt.rva {
+        pe.rva_to_offset(rva)
+            .ok_or_else(|| malformed(format!("RVA {:#x}: not backed by file data", rva)))?
+    } else {
+        return Ok(None);
+    };
+    let len = match opt.length {
+        Some(len) => (len.min(available.into())) as u64,
+        None => available,
+    };
+    Ok(Some((offset, len)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // Two hand-built images with a `.text` section at RVA 0x1000 (file offset 0x200)
+    // and a `.rdata` section at RVA 0x2000 (file offset 0x400).
+    const TINY32: &[u8] = include_bytes!("../../tests/fixtures/tiny32.exe");
+    const TINY64: &[u8] = include_bytes!("../../tests/fixtures/tiny64.exe");
+
+    #[test]
+    fn parse_pe32() {
+        let pe = PeFile::parse(&mut Cursor::new(TINY32)).unwrap();
+        assert_eq!(pe.pe_offset, 0x40);
+        assert_eq!(pe.coff.machine, 0x14c);
+        let opt = pe.optional.as_ref().unwrap();
+        assert!(!opt.is_pe32_plus);
+        assert_eq!(opt.image_base, 0x40_0000);
+        assert_eq!(opt.address_of_entry_point, 0x1000);
+        assert_eq!(opt.data_directories.len(), 16);
+        assert_eq!(
+            opt.data_directories[1],
+            DataDirectory {
+                rva: 0x2000,
+                size: 0x28
+            }
+        );
+        let names: Vec<&str> = pe.sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec![".text", ".rdata"]);
+    }
+
+    #[test]
+    fn parse_pe32_plus() {
+        let pe = PeFile::parse(&mut Cursor::new(TINY64)).unwrap();
+        assert_eq!(pe.coff.machine, 0x8664);
+        let opt = pe.optional.as_ref().unwrap();
+        assert!(opt.is_pe32_plus);
+        assert_eq!(opt.image_base, 0x1_4000_0000);
+        assert_eq!(pe.sections[1].pointer_to_raw_data, 0x400);
+    }
+
+    #[test]
+    fn rva_mapping() {
+        let pe = PeFile::parse(&mut Cursor::new(TINY64)).unwrap();
+        assert_eq!(pe.rva_to_offset(0x1000), Some((0x200, 0x200)));
+        assert_eq!(pe.rva_to_offset(0x2006), Some((0x406, 0x1fa)));
+        assert_eq!(pe.rva_to_offset(0x40), Some((0x40, 0x1c0)));
+        assert_eq!(pe.rva_to_offset(0x2400), None);
+
+        let opt = PeOptions {
+            section: None,
+            rva: Some(0x2000),
+            length: Some(16),
+            input: PathBuf::new(),
+        };
+        let (offset, len) = selected_range(&pe, &opt).unwrap().unwrap();
+        assert_eq!((offset, len), (0x400, 16));
+        assert_eq!(
+            &TINY64[offset as usize..(offset + len) as usize],
+            b"hello from rdata"
+        );
+    }
+
+    #[test]
+    fn malformed_headers() {
+        let err = PeFile::parse(&mut Cursor::new(&b"\x7fELF"[..])).unwrap_err();
+        assert_eq!(err.to_string(), "not a valid PE file");
+
+        let mut data = TINY32.to_vec();
+        data[0x40] = b'X';
+        let err = PeFile::parse(&mut Cursor::new(data)).unwrap_err();
+        assert_eq!(err.to_string(), "malformed PE signature at 0x40");
+
+        let err = PeFile::parse(&mut Cursor::new(&TINY32[..0x150])).unwrap_err();
+        assert_eq!(err.to_string(), "truncated section table at offset 0x138");
+    }
+}
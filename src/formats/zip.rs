@@ -0,0 +1,704 @@
+use super::{read_at, Fields, FormatError, ReadSeek};
+use std::convert::TryFrom;
+use std::io::{self, SeekFrom, Write};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct ZipOptions {
+    /// Dump the compressed bytes of the named entry instead of the directory.
+    #[structopt(long = "entry")]
+    pub entry: Option<String>,
+
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    pub input: PathBuf,
+}
+
+const LOCAL_SIGNATURE: &[u8; 4] = b"PK\x03\x04";
+const CENTRAL_SIGNATURE: &[u8; 4] = b"PK\x01\x02";
+const EOCD_SIGNATURE: &[u8; 4] = b"PK\x05\x06";
+const ZIP64_EOCD_SIGNATURE: &[u8; 4] = b"PK\x06\x06";
+const ZIP64_LOCATOR_SIGNATURE: &[u8; 4] = b"PK\x06\x07";
+const LOCAL_HEADER_SIZE: usize = 30;
+const CENTRAL_HEADER_SIZE: usize = 46;
+const EOCD_SIZE: usize = 22;
+const ZIP64_LOCATOR_SIZE: usize = 20;
+const ZIP64_EOCD_SIZE: usize = 56;
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+/// Sizes and offsets equal to this are stored in the ZIP64 extra field instead.
+const ZIP64_MARKER: u32 = 0xffff_ffff;
+/// Set when the CRC and sizes follow the data rather than the local header.
+const FLAG_DATA_DESCRIPTOR: u16 = 0x0008;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EndOfCentralDirectory {
+    pub offset: u64,
+    /// Offset of the ZIP64 end of central directory record, if there is one.
+    pub zip64_offset: Option<u64>,
+    pub disk: u32,
+    pub entries: u64,
+    pub cd_size: u64,
+    pub cd_offset: u64,
+    pub comment: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalHeader {
+    pub version_needed: u16,
+    pub flags: u16,
+    pub method: u16,
+    pub crc32: u32,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    pub name: Vec<u8>,
+    pub data_offset: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub central_offset: u64,
+    pub version_made_by: u16,
+    pub version_needed: u16,
+    pub flags: u16,
+    pub method: u16,
+    pub crc32: u32,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    pub local_offset: u64,
+    pub name: Vec<u8>,
+    pub comment: Vec<u8>,
+    pub local: Option<LocalHeader>,
+    /// Disagreements between this entry, its local header and the rest of the archive.
+    pub problems: Vec<String>,
+}
+
+impl Entry {
+    pub fn name(&self) -> String {
+        String::from_utf8_lossy(&self.name).into_owned()
+    }
+
+    /// The file range holding this entry's compressed data, if its local header is sound.
+    pub fn data_range(&self) -> Option<(u64, u64)> {
+        self.local
+            .as_ref()
+            .map(|local| (local.data_offset, self.compressed_size))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ZipArchive {
+    pub eocd: EndOfCentralDirectory,
+    /// Bytes before the archive proper, as in self-extracting archives.
+    pub prefix: u64,
+    pub entries: Vec<Entry>,
+    pub problems: Vec<String>,
+}
+
+fn le(data: &[u8]) -> Fields<'_> {
+    Fields {
+        data,
+        big_endian: false,
+    }
+}
+
+/// Replace fields holding `ZIP64_MARKER` by the values from a ZIP64 extra field, in order.
+fn apply_zip64_extra(extra: &[u8], fields: &mut [&mut u64]) -> Result<(), String> {
+    let mut at = 0;
+    while at + 4 <= extra.len() {
+        let f = le(extra);
+        let id = f.u16(at);
+        let size = f.u16(at + 2) as usize;
+        let body = extra
+            .get(at + 4..at + 4 + size)
+            .ok_or_else(|| "extra field runs past its header".to_string())?;
+        if id == ZIP64_EXTRA_ID {
+            let mut values = body.chunks_exact(8).map(|v| le(v).u64(0));
+            for field in fields.iter_mut() {
+                if **field == u64::from(ZIP64_MARKER) {
+                    **field = values
+                        .next()
+                        .ok_or_else(|| "ZIP64 extra field is too short".to_string())?;
+                }
+            }
+            return Ok(());
+        }
+        at += 4 + size;
+    }
+    if fields.iter().any(|f| **f == u64::from(ZIP64_MARKER)) {
+        return Err("sizes need a ZIP64 extra field, but there is none".to_string());
+    }
+    Ok(())
+}
+
+fn find_eocd(reader: &mut dyn ReadSeek) -> Result<EndOfCentralDirectory, FormatError> {
+    let size = reader.seek(SeekFrom::End(0))?;
+    let tail_len = size.min((EOCD_SIZE + 0xffff) as u64);
+    let tail = read_at(reader, size - tail_len, tail_len as usize, "archive")?;
+    // The record may be followed by a comment of up to 64KiB, so search backwards.
+    let at = (0..=tail.len().saturating_sub(EOCD_SIZE))
+        .rev()
+        .find(|&at| {
+            tail[at..].starts_with(EOCD_SIGNATURE)
+                && tail.len() - at >= EOCD_SIZE
+                && at + EOCD_SIZE + le(&tail[at..]).u16(20) as usize <= tail.len()
+        })
+        .ok_or(FormatError::BadMagic("ZIP"))?;
+    let offset = size - tail_len + at as u64;
+    let f = le(&tail[at..]);
+    let comment_len = f.u16(20) as usize;
+    let mut eocd = EndOfCentralDirectory {
+        offset,
+        zip64_offset: None,
+        disk: f.u16(4).into(),
+        entries: f.u16(10).into(),
+        cd_size: f.u32(12).into(),
+        cd_offset: f.u32(16).into(),
+        comment: tail[at + EOCD_SIZE..at + EOCD_SIZE + comment_len].to_vec(),
+    };
+
+    if offset >= ZIP64_LOCATOR_SIZE as u64 {
+        let locator = read_at(
+            reader,
+            offset - ZIP64_LOCATOR_SIZE as u64,
+            ZIP64_LOCATOR_SIZE,
+            "ZIP64 locator",
+        )?;
+        if locator.starts_with(ZIP64_LOCATOR_SIGNATURE) {
+            let record_offset = le(&locator).u64(8);
+            let record = read_at(reader, record_offset, ZIP64_EOCD_SIZE, "ZIP64 end record")?;
+            if !record.starts_with(ZIP64_EOCD_SIGNATURE) {
+                return Err(FormatError::Malformed(format!(
+                    "ZIP64 end of central directory at {:#x}",
+                    record_offset
+                )));
+            }
+            let f = le(&record);
+            eocd.zip64_offset = Some(record_offset);
+            eocd.disk = f.u32(16);
+            eocd.entries = f.u64(32);
+            eocd.cd_size = f.u64(40);
+            eocd.cd_offset = f.u64(48);
+        }
+    }
+    Ok(eocd)
+}
+
+fn parse_local(
+    reader: &mut dyn ReadSeek,
+    offset: u64,
+) -> Result<Result<LocalHeader, String>, FormatError> {
+    let raw = match read_at(reader, offset, LOCAL_HEADER_SIZE, "local header") {
+        Ok(raw) => raw,
+        Err(FormatError::Truncated { .. }) => {
+            return Ok(Err(format!(
+                "local header at {:#x} is past the end of the file",
+                offset
+            )))
+        }
+        Err(e) => return Err(e),
+    };
+    if !raw.starts_with(LOCAL_SIGNATURE) {
+        return Ok(Err(format!("no local header signature at {:#x}", offset)));
+    }
+    let f = le(&raw);
+    let name_len = f.u16(26) as usize;
+    let extra_len = f.u16(28) as usize;
+    let variable = match read_at(
+        reader,
+        offset + LOCAL_HEADER_SIZE as u64,
+        name_len + extra_len,
+        "local header",
+    ) {
+        Ok(variable) => variable,
+        Err(FormatError::Truncated { .. }) => {
+            return Ok(Err(format!("local header at {:#x} is truncated", offset)))
+        }
+        Err(e) => return Err(e),
+    };
+    let mut compressed_size = f.u32(18).into();
+    let mut uncompressed_size = f.u32(22).into();
+    // Local headers carry the sizes only, and both of them, in their ZIP64 field.
+    if compressed_size == u64::from(ZIP64_MARKER) || uncompressed_size == u64::from(ZIP64_MARKER) {
+        compressed_size = ZIP64_MARKER.into();
+        uncompressed_size = ZIP64_MARKER.into();
+        if let Err(problem) = apply_zip64_extra(
+            &variable[name_len..],
+            &mut [&mut uncompressed_size, &mut compressed_size],
+        ) {
+            return Ok(Err(format!("local header: {}", problem)));
+        }
+    }
+    Ok(Ok(LocalHeader {
+        version_needed: f.u16(4),
+        flags: f.u16(6),
+        method: f.u16(8),
+        crc32: f.u32(14),
+        compressed_size,
+        uncompressed_size,
+        name: variable[..name_len].to_vec(),
+        data_offset: offset + (LOCAL_HEADER_SIZE + name_len + extra_len) as u64,
+    }))
+}
+
+/// Compare an entry with its local header, noting every disagreement.
+fn check_local(entry: &mut Entry, local: &LocalHeader, cd_start: u64) {
+    let problems = &mut entry.problems;
+    if local.name != entry.name {
+        problems.push(format!(
+            "local name {:?} differs from central name",
+            String::from_utf8_lossy(&local.name)
+        ));
+    }
+    if local.method != entry.method {
+        problems.push(format!(
+            "local method {} differs from central {}",
+            method_name(local.method),
+            method_name(entry.method)
+        ));
+    }
+    if local.flags != entry.flags {
+        problems.push(format!(
+            "local flags {:#06x} differ from central {:#06x}",
+            local.flags, entry.flags
+        ));
+    }
+    // With a data descriptor the local header may leave these zero.
+    let descriptor = local.flags & FLAG_DATA_DESCRIPTOR != 0;
+    if !(descriptor && local.crc32 == 0) && local.crc32 != entry.crc32 {
+        problems.push(format!(
+            "local CRC32 {:#010x} differs from central {:#010x}",
+            local.crc32, entry.crc32
+        ));
+    }
+    if !(descriptor && local.compressed_size == 0) && local.compressed_size != entry.compressed_size
+    {
+        problems.push(format!(
+            "local compressed size {} differs from central {}",
+            local.compressed_size, entry.compressed_size
+        ));
+    }
+    if !(descriptor && local.uncompressed_size == 0)
+        && local.uncompressed_size != entry.uncompressed_size
+    {
+        problems.push(format!(
+            "local uncompressed size {} differs from central {}",
+            local.uncompressed_size, entry.uncompressed_size
+        ));
+    }
+    if local
+        .data_offset
+        .checked_add(entry.compressed_size)
+        .is_none_or(|end| end > cd_start)
+    {
+        problems.push(format!(
+            "data at {:#x} runs into the central directory at {:#x}",
+            local.data_offset, cd_start
+        ));
+    }
+}
+
+impl ZipArchive {
+    pub fn parse(reader: &mut dyn ReadSeek) -> Result<ZipArchive, FormatError> {
+        let eocd = find_eocd(reader)?;
+        let mut problems = vec![];
+
+        // Archives with data prepended still record offsets from their own start.
+        let expected = eocd.zip64_offset.unwrap_or(eocd.offset);
+        let mut prefix = 0;
+        if let Some(actual) = expected.checked_sub(eocd.cd_size) {
+            if actual != eocd.cd_offset {
+                let at_recorded = read_at(reader, eocd.cd_offset, 4, "central directory")
+                    .map(|sig| &sig[..] == CENTRAL_SIGNATURE)
+                    .unwrap_or(false);
+                if !at_recorded && actual > eocd.cd_offset {
+                    prefix = actual - eocd.cd_offset;
+                    problems.push(format!(
+                        "central directory is at {:#x}, not {:#x}; assuming {} bytes of prepended data",
+                        actual, eocd.cd_offset, prefix
+                    ));
+                }
+            }
+        }
+
+        let cd_start = eocd.cd_offset + prefix;
+        let cd_len = usize::try_from(eocd.cd_size).map_err(|_| {
+            FormatError::Malformed(format!("central directory size {}", eocd.cd_size))
+        })?;
+        let cd = read_at(reader, cd_start, cd_len, "central directory")?;
+
+        let mut entries = vec![];
+        let mut at = 0;
+        while at < cd.len() {
+            if cd.len() - at < CENTRAL_HEADER_SIZE || !cd[at..].starts_with(CENTRAL_SIGNATURE) {
+                problems.push(format!(
+                    "no central directory header at {:#x}",
+                    cd_start + at as u64
+                ));
+                break;
+            }
+            let f = le(&cd[at..]);
+            let name_len = f.u16(28) as usize;
+            let extra_len = f.u16(30) as usize;
+            let comment_len = f.u16(32) as usize;
+            let end = at + CENTRAL_HEADER_SIZE + name_len + extra_len + comment_len;
+            if end > cd.len() {
+                problems.push(format!(
+                    "central directory header at {:#x} runs past the directory",
+                    cd_start + at as u64
+                ));
+                break;
+            }
+            let name_at = at + CENTRAL_HEADER_SIZE;
+            let extra = &cd[name_at + name_len..name_at + name_len + extra_len];
+            let mut entry = Entry {
+                central_offset: cd_start + at as u64,
+                version_made_by: f.u16(4),
+                version_needed: f.u16(6),
+                flags: f.u16(8),
+                method: f.u16(10),
+                crc32: f.u32(16),
+                compressed_size: f.u32(20).into(),
+                uncompressed_size: f.u32(24).into(),
+                local_offset: f.u32(42).into(),
+                name: cd[name_at..name_at + name_len].to_vec(),
+                comment: cd[end - comment_len..end].to_vec(),
+                local: None,
+                problems: vec![],
+            };
+            let zip64 = apply_zip64_extra(
+                extra,
+                &mut [
+                    &mut entry.uncompressed_size,
+                    &mut entry.compressed_size,
+                    &mut entry.local_offset,
+                ],
+            );
+            match zip64 {
+                Ok(()) => match entry.local_offset.checked_add(prefix) {
+                    Some(offset) => match parse_local(reader, offset)? {
+                        Ok(local) => {
+                            check_local(&mut entry, &local, cd_start);
+                            entry.local = Some(local);
+                        }
+                        Err(problem) => entry.problems.push(problem),
+                    },
+                    None => entry.problems.push(format!(
+                        "local header offset {:#x} is out of range",
+                        entry.local_offset
+                    )),
+                },
+                Err(problem) => entry.problems.push(problem),
+            }
+            entries.push(entry);
+            at = end;
+        }
+
+        if entries.len() as u64 != eocd.entries {
+            problems.push(format!(
+                "end of central directory counts {} entries, but the directory holds {}",
+                eocd.entries,
+                entries.len()
+            ));
+        }
+
+        Ok(ZipArchive {
+            eocd,
+            prefix,
+            entries,
+            problems,
+        })
+    }
+
+    pub fn entry(&self, name: &str) -> Option<&Entry> {
+        self.entries.iter().find(|e| e.name == name.as_bytes())
+    }
+}
+
+pub fn method_name(method: u16) -> String {
+    let name = match method {
+        0 => "stored",
+        1 => "shrunk",
+        6 => "imploded",
+        8 => "deflate",
+        9 => "deflate64",
+        12 => "bzip2",
+        14 => "lzma",
+        93 => "zstd",
+        95 => "xz",
+        98 => "ppmd",
+        99 => "aes",
+        _ => return format!("method {}", method),
+    };
+    name.to_string()
+}
+
+/// The file range of the compressed data of entry `name`.
+pub fn entry_range(archive: &ZipArchive, name: &str) -> Result<(u64, u64), FormatError> {
+    let entry = archive
+        .entry(name)
+        .ok_or_else(|| FormatError::NoEntry(name.to_string()))?;
+    entry.data_range().ok_or_else(|| {
+        FormatError::Malformed(format!(
+            "local header for {:?}: {}",
+            name,
+            entry.problems.join("; ")
+        ))
+    })
+}
+
+/// Print the end of central directory record, the entries and any inconsistencies.
+pub fn print_zip(archive: &ZipArchive, w: &mut dyn Write) -> io::Result<()> {
+    let eocd = &archive.eocd;
+    writeln!(w, "End of central directory:\t{:#010x}", eocd.offset)?;
+    match eocd.zip64_offset {
+        Some(offset) => writeln!(w, "ZIP64 end record:\t\t{:#010x}", offset)?,
+        None => writeln!(w, "ZIP64 end record:\t\tnone")?,
+    }
+    writeln!(w, "Disk:\t\t\t\t{}", eocd.disk)?;
+    writeln!(w, "Entries:\t\t\t{}", eocd.entries)?;
+    writeln!(
+        w,
+        "Central directory:\t\t{:#010x} ({} bytes)",
+        eocd.cd_offset + archive.prefix,
+        eocd.cd_size
+    )?;
+    if !eocd.comment.is_empty() {
+        writeln!(
+            w,
+            "Comment:\t\t\t{:?}",
+            String::from_utf8_lossy(&eocd.comment)
+        )?;
+    }
+
+    writeln!(w)?;
+    writeln!(
+        w,
+        "{:>4}  {:<12}{:<12}{:<10}{:<8}{:<12}{:<12}{:<12}Name",
+        "#", "Local", "Data", "Method", "Flags", "CRC32", "Compressed", "Size"
+    )?;
+    for (i, entry) in archive.entries.iter().enumerate() {
+        let local = match entry.local_offset.checked_add(archive.prefix) {
+            Some(offset) => format!("{:#x}", offset),
+            None => "-".to_string(),
+        };
+        let data = match entry.data_range() {
+            Some((offset, _)) => format!("{:#x}", offset),
+            None => "-".to_string(),
+        };
+        writeln!(
+            w,
+            "{:>4}  {:<12}{:<12}{:<10}{:<#8x}{:<#12x}{:<12}{:<12}{}",
+            i,
+            local,
+            data,
+            method_name(entry.method),
+            entry.flags,
+            entry.crc32,
+            entry.compressed_size,
+            entry.uncompressed_size,
+            entry.name()
+        )?;
+    }
+
+    let count = archive.problems.len()
+        + archive
+            .entries
+            .iter()
+            .map(|e| e.problems.len())
+            .sum::<usize>();
+    writeln!(w)?;
+    if count == 0 {
+        return writeln!(w, "No inconsistencies found.");
+    }
+    writeln!(w, "Inconsistencies: {}", count)?;
+    for problem in &archive.problems {
+        writeln!(w, "  archive: {}", problem)?;
+    }
+    for (i, entry) in archive.entries.iter().enumerate() {
+        for problem in &entry.problems {
+            writeln!(w, "  entry {} ({}): {}", i, entry.name(), problem)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Build an archive of stored entries, optionally with ZIP64 records throughout.
+    fn build(entries: &[(&str, &[u8])], zip64: bool) -> Vec<u8> {
+        let mut out = vec![];
+        let mut central = vec![];
+        for (name, data) in entries {
+            let offset = out.len() as u64;
+            let crc = crc32fast::hash(data);
+            let size = data.len() as u32;
+
+            out.extend_from_slice(LOCAL_SIGNATURE);
+            out.extend_from_slice(&[20, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            out.extend_from_slice(&crc.to_le_bytes());
+            out.extend_from_slice(&size.to_le_bytes());
+            out.extend_from_slice(&size.to_le_bytes());
+            out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            out.extend_from_slice(&[0, 0]);
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(data);
+
+            central.extend_from_slice(CENTRAL_SIGNATURE);
+            central.extend_from_slice(&[45, 3, 20, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            central.extend_from_slice(&crc.to_le_bytes());
+            if zip64 {
+                central.extend_from_slice(&ZIP64_MARKER.to_le_bytes());
+                central.extend_from_slice(&ZIP64_MARKER.to_le_bytes());
+            } else {
+                central.extend_from_slice(&size.to_le_bytes());
+                central.extend_from_slice(&size.to_le_bytes());
+            }
+            central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            central.extend_from_slice(&[if zip64 { 28 } else { 0 }, 0]);
+            central.extend_from_slice(&[0; 10]);
+            if zip64 {
+                central.extend_from_slice(&ZIP64_MARKER.to_le_bytes());
+            } else {
+                central.extend_from_slice(&(offset as u32).to_le_bytes());
+            }
+            central.extend_from_slice(name.as_bytes());
+            if zip64 {
+                central.extend_from_slice(&[1, 0, 24, 0]);
+                central.extend_from_slice(&u64::from(size).to_le_bytes());
+                central.extend_from_slice(&u64::from(size).to_le_bytes());
+                central.extend_from_slice(&offset.to_le_bytes());
+            }
+        }
+        let cd_offset = out.len() as u64;
+        out.extend_from_slice(&central);
+        let count = entries.len() as u64;
+        if zip64 {
+            let record = out.len() as u64;
+            out.extend_from_slice(ZIP64_EOCD_SIGNATURE);
+            out.extend_from_slice(&44u64.to_le_bytes());
+            out.extend_from_slice(&[45, 3, 45, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            out.extend_from_slice(&count.to_le_bytes());
+            out.extend_from_slice(&count.to_le_bytes());
+            out.extend_from_slice(&(central.len() as u64).to_le_bytes());
+            out.extend_from_slice(&cd_offset.to_le_bytes());
+            out.extend_from_slice(ZIP64_LOCATOR_SIGNATURE);
+            out.extend_from_slice(&[0; 4]);
+            out.extend_from_slice(&record.to_le_bytes());
+            out.extend_from_slice(&1u32.to_le_bytes());
+        }
+        out.extend_from_slice(EOCD_SIGNATURE);
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&(count as u16).to_le_bytes());
+        out.extend_from_slice(&(count as u16).to_le_bytes());
+        out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+        if zip64 {
+            out.extend_from_slice(&ZIP64_MARKER.to_le_bytes());
+        } else {
+            out.extend_from_slice(&(cd_offset as u32).to_le_bytes());
+        }
+        out.extend_from_slice(&[2, 0]);
+        out.extend_from_slice(b"hi");
+        out
+    }
+
+    const ENTRIES: [(&str, &[u8]); 2] = [("a.txt", b"hello"), ("dir/b.bin", b"\x00\x01\x02")];
+
+    #[test]
+    fn parse_archive() {
+        let data = build(&ENTRIES, false);
+        let archive = ZipArchive::parse(&mut Cursor::new(&data)).unwrap();
+        assert_eq!(archive.eocd.zip64_offset, None);
+        assert_eq!(archive.eocd.entries, 2);
+        assert_eq!(archive.eocd.comment, b"hi");
+        let names: Vec<String> = archive.entries.iter().map(Entry::name).collect();
+        assert_eq!(names, vec!["a.txt", "dir/b.bin"]);
+        assert!(archive.problems.is_empty());
+        assert!(archive.entries.iter().all(|e| e.problems.is_empty()));
+
+        let (offset, len) = entry_range(&archive, "a.txt").unwrap();
+        assert_eq!(&data[offset as usize..(offset + len) as usize], b"hello");
+        assert_eq!(
+            entry_range(&archive, "missing").unwrap_err().to_string(),
+            "no entry named \"missing\""
+        );
+    }
+
+    #[test]
+    fn parse_zip64() {
+        let data = build(&ENTRIES, true);
+        let archive = ZipArchive::parse(&mut Cursor::new(&data)).unwrap();
+        assert!(archive.eocd.zip64_offset.is_some());
+        let entry = archive.entry("dir/b.bin").unwrap();
+        assert_eq!(entry.compressed_size, 3);
+        assert_eq!(entry.local_offset, 0x28);
+        assert!(entry.problems.is_empty());
+        assert_eq!(entry.data_range(), Some((0x28 + 39, 3)));
+    }
+
+    #[test]
+    fn report_inconsistencies() {
+        let mut data = build(&ENTRIES, false);
+        data[14] ^= 0xff; // first local CRC32
+        data[0x28] = b'X'; // second local signature
+        let archive = ZipArchive::parse(&mut Cursor::new(&data)).unwrap();
+        assert_eq!(archive.entries[0].problems.len(), 1);
+        assert!(archive.entries[0].problems[0].starts_with("local CRC32"));
+        assert_eq!(
+            archive.entries[1].problems,
+            vec!["no local header signature at 0x28"]
+        );
+        assert!(entry_range(&archive, "dir/b.bin").is_err());
+
+        // Prepended data shifts every offset.
+        let mut shifted = b"#!/bin/sh\n".to_vec();
+        shifted.extend(build(&ENTRIES, false));
+        let archive = ZipArchive::parse(&mut Cursor::new(&shifted)).unwrap();
+        assert_eq!(archive.prefix, 10);
+        assert_eq!(archive.problems.len(), 1);
+        let (offset, _) = entry_range(&archive, "a.txt").unwrap();
+        assert_eq!(&shifted[offset as usize..offset as usize + 5], b"hello");
+
+        let err = ZipArchive::parse(&mut Cursor::new(&b"not a zip"[..])).unwrap_err();
+        assert_eq!(err.to_string(), "not a valid ZIP file");
+    }
+
+    #[test]
+    fn local_offset_out_of_range() {
+        // A ZIP64 local header offset that overflows once the prefix is added.
+        let mut data = b"#!/bin/sh\n".to_vec();
+        data.extend(build(&ENTRIES, true));
+        let at = 10 + 82 + 79 + 46 + 9 + 20;
+        assert_eq!(&data[at..at + 8], &0x28u64.to_le_bytes());
+        data[at..at + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        // The locator gives where the ZIP64 end record really is.
+        let locator = data.len() - EOCD_SIZE - 2 - 20;
+        let record = le(&data[locator..]).u64(8);
+        data[locator + 8..locator + 16].copy_from_slice(&(record + 10).to_le_bytes());
+        let archive = ZipArchive::parse(&mut Cursor::new(&data)).unwrap();
+        assert_eq!(
+            archive.entries[1].problems,
+            vec!["local header offset 0xffffffffffffffff is out of range"]
+        );
+        assert_eq!(archive.entries[1].data_range(), None);
+
+        let mut out = vec![];
+        print_zip(&archive, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("\n   1  -           -           "));
+    }
+
+    #[test]
+    fn truncated_end_record() {
+        // Files shorter than an end record that still start with its signature.
+        let full = build(&ENTRIES, false);
+        let eocd = &full[full.len() - EOCD_SIZE - 2..];
+        for len in [4, 20, EOCD_SIZE - 1] {
+            let err = ZipArchive::parse(&mut Cursor::new(&eocd[..len])).unwrap_err();
+            assert_eq!(err.to_string(), "not a valid ZIP file", "{} bytes", len);
+        }
+    }
+}
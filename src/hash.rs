@@ -0,0 +1,302 @@
+use crate::{byte_unit::parse_block_size, read_to_fill, RangeOptions};
+use crc::{Crc, CRC_16_ARC, CRC_16_IBM_3740, CRC_16_KERMIT, CRC_16_MODBUS, CRC_16_XMODEM};
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use structopt::StructOpt;
+use thiserror::Error;
+
+#[derive(Debug, StructOpt)]
+pub struct HashOptions {
+    /// Algorithms to compute (crc32, crc16-arc, crc16-ccitt, crc16-xmodem, crc16-modbus,
+    /// crc16-kermit, adler32, md5, sha1, sha256). Defaults to all of them.
+    #[structopt(short = "a", long = "algorithm", use_delimiter = true)]
+    pub algorithms: Vec<Algorithm>,
+    /// Print a hash for every block of `per-block` bytes.
+    #[structopt(long = "per-block", parse(try_from_str = parse_block_size))]
+    pub per_block: Option<usize>,
+
+    #[structopt(flatten)]
+    pub range: RangeOptions,
+
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    pub input: PathBuf,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Algorithm {
+    Crc32,
+    Crc16Arc,
+    Crc16Ccitt,
+    Crc16Xmodem,
+    Crc16Modbus,
+    Crc16Kermit,
+    Adler32,
+    Md5,
+    Sha1,
+    Sha256,
+}
+
+impl Algorithm {
+    pub const ALL: [Algorithm; 10] = [
+        Algorithm::Crc32,
+        Algorithm::Crc16Arc,
+        Algorithm::Crc16Ccitt,
+        Algorithm::Crc16Xmodem,
+        Algorithm::Crc16Modbus,
+        Algorithm::Crc16Kermit,
+        Algorithm::Adler32,
+        Algorithm::Md5,
+        Algorithm::Sha1,
+        Algorithm::Sha256,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Crc32 => "crc32",
+            Algorithm::Crc16Arc => "crc16-arc",
+            Algorithm::Crc16Ccitt => "crc16-ccitt",
+            Algorithm::Crc16Xmodem => "crc16-xmodem",
+            Algorithm::Crc16Modbus => "crc16-modbus",
+            Algorithm::Crc16Kermit => "crc16-kermit",
+            Algorithm::Adler32 => "adler32",
+            Algorithm::Md5 => "md5",
+            Algorithm::Sha1 => "sha1",
+            Algorithm::Sha256 => "sha256",
+        }
+    }
+}
+
+impl Display for Algorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, Error)]
+#[error("unknown hash algorithm {0:?}")]
+pub struct UnknownAlgorithmError(String);
+
+impl FromStr for Algorithm {
+    type Err = UnknownAlgorithmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized = s.trim().to_ascii_lowercase();
+        Algorithm::ALL
+            .iter()
+            .copied()
+            .find(|a| a.name() == normalized || a.name().replace('-', "") == normalized)
+            .ok_or_else(|| UnknownAlgorithmError(s.to_string()))
+    }
+}
+
+static CRC16_ARC: Crc<u16> = Crc::<u16>::new(&CRC_16_ARC);
+static CRC16_CCITT: Crc<u16> = Crc::<u16>::new(&CRC_16_IBM_3740);
+static CRC16_XMODEM: Crc<u16> = Crc::<u16>::new(&CRC_16_XMODEM);
+static CRC16_MODBUS: Crc<u16> = Crc::<u16>::new(&CRC_16_MODBUS);
+static CRC16_KERMIT: Crc<u16> = Crc::<u16>::new(&CRC_16_KERMIT);
+
+/// A running checksum or digest for one `Algorithm`.
+pub enum Hasher {
+    Crc32(crc32fast::Hasher),
+    Crc16(crc::Digest<'static, u16>),
+    Adler32(adler::Adler32),
+    Md5(Md5),
+    Sha1(Sha1),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    pub fn new(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Crc32 => Hasher::Crc32(crc32fast::Hasher::new()),
+            Algorithm::Crc16Arc => Hasher::Crc16(CRC16_ARC.digest()),
+            Algorithm::Crc16Ccitt => Hasher::Crc16(CRC16_CCITT.digest()),
+            Algorithm::Crc16Xmodem => Hasher::Crc16(CRC16_XMODEM.digest()),
+            Algorithm::Crc16Modbus => Hasher::Crc16(CRC16_MODBUS.digest()),
+            Algorithm::Crc16Kermit => Hasher::Crc16(CRC16_KERMIT.digest()),
+            Algorithm::Adler32 => Hasher::Adler32(adler::Adler32::new()),
+            Algorithm::Md5 => Hasher::Md5(Md5::new()),
+            Algorithm::Sha1 => Hasher::Sha1(Sha1::new()),
+            Algorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+        }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Hasher::Crc32(h) => h.update(bytes),
+            Hasher::Crc16(h) => h.update(bytes),
+            Hasher::Adler32(h) => h.write_slice(bytes),
+            Hasher::Md5(h) => h.update(bytes),
+            Hasher::Sha1(h) => h.update(bytes),
+            Hasher::Sha256(h) => h.update(bytes),
+        }
+    }
+
+    /// The final value, most significant byte first.
+    pub fn finish(self) -> Vec<u8> {
+        match self {
+            Hasher::Crc32(h) => h.finalize().to_be_bytes().to_vec(),
+            Hasher::Crc16(h) => h.finalize().to_be_bytes().to_vec(),
+            Hasher::Adler32(h) => h.checksum().to_be_bytes().to_vec(),
+            Hasher::Md5(h) => h.finalize().to_vec(),
+            Hasher::Sha1(h) => h.finalize().to_vec(),
+            Hasher::Sha256(h) => h.finalize().to_vec(),
+        }
+    }
+}
+
+/// Compute `algorithm` over `bytes` and return it as lowercase hex.
+pub fn hash_hex(algorithm: Algorithm, bytes: &[u8]) -> String {
+    let mut hasher = Hasher::new(algorithm);
+    hasher.update(bytes);
+    to_hex(&hasher.finish())
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Bytes read at a time.
+const READ_SIZE: usize = 64 << 10;
+
+/// Print the selected checksums of `reader`, either once for the whole input or
+/// once per block.
+pub fn print_hashes(
+    opt: &HashOptions,
+    offset: u128,
+    reader: &mut dyn io::Read,
+    w: &mut dyn Write,
+) -> io::Result<()> {
+    let algorithms: &[Algorithm] = if opt.algorithms.is_empty() {
+        &Algorithm::ALL
+    } else {
+        &opt.algorithms
+    };
+    let width = algorithms.iter().map(|a| a.name().len()).max().unwrap_or(0);
+
+    let mut buffer = vec![0u8; READ_SIZE];
+    if let Some(block) = opt.per_block {
+        let mut idx = 0;
+        loop {
+            // Hash a block a buffer at a time rather than holding it all.
+            let mut hashers: Vec<Hasher> = algorithms.iter().map(|&a| Hasher::new(a)).collect();
+            let mut left = block;
+            while left > 0 {
+                let amt = read_to_fill(reader, &mut buffer[..left.min(READ_SIZE)])?;
+                if amt == 0 {
+                    break;
+                }
+                for hasher in hashers.iter_mut() {
+                    hasher.update(&buffer[..amt]);
+                }
+                left -= amt;
+            }
+            if left == block {
+                return Ok(());
+            }
+            for (algorithm, hasher) in algorithms.iter().zip(hashers) {
+                writeln!(
+                    w,
+                    "{:#010x}\t{:<width$}  {}",
+                    offset + idx * block as u128,
+                    algorithm.name(),
+                    to_hex(&hasher.finish()),
+                    width = width
+                )?;
+            }
+            idx += 1;
+        }
+    }
+
+    let mut hashers: Vec<Hasher> = algorithms.iter().map(|&a| Hasher::new(a)).collect();
+    loop {
+        let amt = read_to_fill(reader, &mut buffer)?;
+        if amt == 0 {
+            break;
+        }
+        for hasher in hashers.iter_mut() {
+            hasher.update(&buffer[..amt]);
+        }
+    }
+    for (algorithm, hasher) in algorithms.iter().zip(hashers) {
+        writeln!(
+            w,
+            "{:<width$}  {}",
+            algorithm.name(),
+            to_hex(&hasher.finish()),
+            width = width
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_values() {
+        // The standard "123456789" check values for each algorithm.
+        let data = b"123456789";
+        assert_eq!(hash_hex(Algorithm::Crc32, data), "cbf43926");
+        assert_eq!(hash_hex(Algorithm::Crc16Arc, data), "bb3d");
+        assert_eq!(hash_hex(Algorithm::Crc16Ccitt, data), "29b1");
+        assert_eq!(hash_hex(Algorithm::Crc16Xmodem, data), "31c3");
+        assert_eq!(hash_hex(Algorithm::Crc16Modbus, data), "4b37");
+        assert_eq!(hash_hex(Algorithm::Crc16Kermit, data), "2189");
+        assert_eq!(hash_hex(Algorithm::Adler32, data), "091e01de");
+    }
+
+    #[test]
+    fn digests() {
+        assert_eq!(
+            hash_hex(Algorithm::Md5, b"abc"),
+            "900150983cd24fb0d6963f7d28e17f72"
+        );
+        assert_eq!(
+            hash_hex(Algorithm::Sha1, b"abc"),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            hash_hex(Algorithm::Sha256, b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn per_block_larger_than_a_read() {
+        let opt = HashOptions::from_iter(&["hash", "-a", "crc32", "--per-block", "100000", "in"]);
+        let data: Vec<u8> = (0..150_000u32).map(|i| (i % 251) as u8).collect();
+        let mut out = vec![];
+        print_hashes(&opt, 0, &mut &data[..], &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "0x00000000\tcrc32  {}\n0x000186a0\tcrc32  {}\n",
+                hash_hex(Algorithm::Crc32, &data[..100_000]),
+                hash_hex(Algorithm::Crc32, &data[100_000..])
+            )
+        );
+
+        let err = HashOptions::from_iter_safe(&["hash", "--per-block", "0", "in"]).unwrap_err();
+        assert!(err
+            .message
+            .contains("the block size must be from 1 B to 1 GiB"));
+    }
+
+    #[test]
+    fn parse_algorithm() {
+        assert_eq!("CRC32".parse::<Algorithm>().unwrap(), Algorithm::Crc32);
+        assert!("whirlpool".parse::<Algorithm>().is_err());
+        assert_eq!(
+            "crc16xmodem".parse::<Algorithm>().unwrap(),
+            Algorithm::Crc16Xmodem
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Input sources: regular files mapped into memory, or a stream for everything else.
+
+use crate::{
+    open_range, print_lines_to, print_slice_parallel, print_slice_to, LineWriter, RangeOptions,
+    Tail,
+};
+use memmap2::Mmap;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::path::Path;
+
+pub enum Input {
+    /// A regular file mapped into memory, of which `position..end` is still to be read.
+    Mapped {
+        map: Mmap,
+        position: usize,
+        end: usize,
+    },
+    Stream(Box<dyn Read>),
+}
+
+impl Input {
+    /// Open `path` limited to `range`, returning the input with the offset of its first
+    /// byte. Non-empty regular files are mapped when `mmap` is set; pipes, devices and
+    /// compressed input are streamed.
+    pub fn open(path: &Path, range: &RangeOptions, mmap: bool) -> io::Result<(Input, u128)> {
+        if mmap && !range.decompress {
+            let file = fs::File::open(path)?;
+            let metadata = file.metadata()?;
+            if metadata.is_file() && metadata.len() > 0 {
+                // Safety: the map is read-only. Should another process truncate the
+                // file while it is mapped, reading the lost pages faults instead of
+                // returning an error; every mmap-based reader shares this caveat.
+                if let Ok(map) = unsafe { Mmap::map(&file) } {
+                    let len = map.len();
+                    let skip = range.skip.unwrap_or(0);
+                    let position = usize::try_from(skip).unwrap_or(len).min(len);
+                    let end = match range.length {
+                        Some(n) => {
+                            usize::try_from(n).map_or(len, |n| position.saturating_add(n).min(len))
+                        }
+                        None => len,
+                    };
+                    return Ok((Input::Mapped { map, position, end }, skip));
+                }
+            }
+        }
+        let (reader, offset) = open_range(path, range)?;
+        Ok((Input::Stream(reader), offset))
+    }
+
+    /// The unread bytes, if the input is mapped.
+    pub fn as_slice(&self) -> Option<&[u8]> {
+        match self {
+            Input::Mapped { map, position, end } => Some(&map[*position..*end]),
+            Input::Stream(_) => None,
+        }
+    }
+
+    /// Up to `len` of the next bytes, without consuming them.
+    pub fn peek(&mut self, len: usize) -> io::Result<Vec<u8>> {
+        if let Some(data) = self.as_slice() {
+            return Ok(data[..len.min(data.len())].to_vec());
+        }
+        let mut head = vec![];
+        if let Input::Stream(ref mut reader) = self {
+            reader.take(len as u64).read_to_end(&mut head)?;
+            let rest = mem::replace(reader, Box::new(io::empty()));
+            *reader = Box::new(io::Cursor::new(head.clone()).chain(rest));
+        }
+        Ok(head)
+    }
+
+    /// Drop all but `tail` of the remaining input, its first row starting `lead` bytes
+    /// ahead of it, returning how many bytes were dropped. Streams are read to the end,
+    /// holding on to only as much as the tail can take up.
+    pub fn tail(&mut self, tail: Tail, row_size: usize, lead: usize) -> io::Result<u128> {
+        match self {
+            Input::Mapped { position, end, .. } => {
+                let dropped = tail.start((*end - *position) as u128, row_size, lead);
+                *position += dropped as usize;
+                Ok(dropped)
+            }
+            Input::Stream(reader) => {
+                let keep = usize::try_from(tail.max_len(row_size)).unwrap_or(usize::MAX);
+                let mut ring = VecDeque::new();
+                let mut total = 0u128;
+                let mut buf = vec![0u8; 64 << 10];
+                loop {
+                    let amt = match reader.read(&mut buf) {
+                        Ok(0) => break,
+                        Ok(amt) => amt,
+                        Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(e) => return Err(e),
+                    };
+                    total += amt as u128;
+                    ring.extend(&buf[..amt]);
+                    if ring.len() > keep {
+                        ring.drain(..ring.len() - keep);
+                    }
+                }
+                let dropped = tail.start(total, row_size, lead);
+                let kept = (total - dropped) as usize;
+                ring.drain(..ring.len() - kept);
+                *reader = Box::new(io::Cursor::new(Vec::from(ring)));
+                Ok(dropped)
+            }
+        }
+    }
+
+    /// Render the remaining input with `writers`, the first byte being at `offset`.
+    pub fn print(
+        &mut self,
+        writers: &[Box<dyn LineWriter>],
+        offset: u128,
+        w: &mut dyn Write,
+    ) -> io::Result<()> {
+        match self {
+            Input::Mapped { map, position, end } => {
+                let data = &map[*position..*end];
+                *position = *end;
+                print_slice_to(writers, offset, data, w)
+            }
+            Input::Stream(reader) => print_lines_to(writers, offset, reader, w),
+        }
+    }
+
+    /// Like `print`, rendering mapped input with `threads` threads, each using
+    /// writers from `make_writers`. Streams are rendered by the calling thread.
+    pub fn print_parallel(
+        &mut self,
+        make_writers: &(dyn Fn() -> Vec<Box<dyn LineWriter>> + Sync),
+        offset: u128,
+        threads: usize,
+        w: &mut dyn Write,
+    ) -> io::Result<()> {
+        match self {
+            Input::Mapped { map, position, end } => {
+                let data = &map[*position..*end];
+                *position = *end;
+                print_slice_parallel(make_writers, offset, data, threads, w)
+            }
+            Input::Stream(_) => self.print(&make_writers(), offset, w),
+        }
+    }
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Input::Mapped { map, position, end } => {
+                let n = buf.len().min(*end - *position);
+                buf[..n].copy_from_slice(&map[*position..*position + n]);
+                *position += n;
+                Ok(n)
+            }
+            Input::Stream(reader) => reader.read(buf),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CanonicalWriter;
+
+    #[test]
+    fn mapped_and_streamed_render_alike() {
+        let path = std::env::temp_dir().join(format!("hex-input-{}.bin", std::process::id()));
+        let data: Vec<u8> = (0..=255u8).cycle().take(1000).collect();
+        fs::write(&path, &data).unwrap();
+        let range = RangeOptions {
+            length: Some(100),
+            skip: Some(0x30),
+            decompress: false,
+        };
+        let writers: Vec<Box<dyn LineWriter>> = vec![Box::new(CanonicalWriter::default())];
+
+        let mut outputs = vec![];
+        for &mmap in &[true, false] {
+            let (mut input, offset) = Input::open(&path, &range, mmap).unwrap();
+            assert_eq!(input.as_slice().is_some(), mmap);
+            assert_eq!(input.peek(4).unwrap(), vec![0x30, 0x31, 0x32, 0x33]);
+            let mut out = vec![];
+            input.print(&writers, offset, &mut out).unwrap();
+            outputs.push(out);
+        }
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(outputs[0], outputs[1]);
+        let text = String::from_utf8(outputs.remove(0)).unwrap();
+        assert_eq!(text.lines().count(), 7);
+        assert!(text.starts_with("0x00000030\t30 31"));
+    }
+
+    #[test]
+    fn tail_of_mapped_and_streamed_input() {
+        let data: Vec<u8> = (0..100).collect();
+        let path = std::env::temp_dir().join(format!("hex-tail-{}.bin", std::process::id()));
+        fs::write(&path, &data).unwrap();
+        let range = RangeOptions {
+            length: None,
+            skip: None,
+            decompress: false,
+        };
+        for &tail in &[
+            Tail::Bytes(10),
+            Tail::Rows(3),
+            Tail::Bytes(0),
+            Tail::Rows(0),
+            Tail::Bytes(200),
+        ] {
+            let (mut mapped, _) = Input::open(&path, &range, true).unwrap();
+            let mut stream = Input::Stream(Box::new(io::Cursor::new(data.clone())));
+            let dropped = tail.start(100, 16, 0);
+            for input in [&mut mapped, &mut stream] {
+                assert_eq!(input.tail(tail, 16, 0).unwrap(), dropped, "{:?}", tail);
+                let mut rest = vec![];
+                input.read_to_end(&mut rest).unwrap();
+                assert_eq!(rest, &data[dropped as usize..], "{:?}", tail);
+            }
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn parallel_render_keeps_order() {
+        let data: Vec<u8> = (0..3_000_000u32).map(|i| (i % 253) as u8).collect();
+        let make_writers =
+            || -> Vec<Box<dyn LineWriter>> { vec![Box::new(CanonicalWriter::default())] };
+        let mut serial = vec![];
+        print_slice_to(&make_writers(), 0x100, &data, &mut serial).unwrap();
+        for &threads in &[2, 3, 8] {
+            let mut parallel = vec![];
+            print_slice_parallel(&make_writers, 0x100, &data, threads, &mut parallel).unwrap();
+            assert!(parallel == serial, "{} threads", threads);
+        }
+    }
+}
@@ -1,17 +1,45 @@
 #![warn(clippy::all)]
 
+pub mod archive;
 mod byte_unit;
+pub mod charset;
+pub mod compat;
+pub mod decompress;
+pub mod device;
+pub mod follow;
+pub mod format_string;
+pub mod formats;
+pub mod hash;
+pub mod input;
+pub mod magic;
+pub mod od;
+mod render;
+pub mod stats;
+pub mod strings;
+pub mod template;
 
 use byte_unit::parse_bytes;
 use byteorder::{ByteOrder, NativeEndian};
-use itertools::Itertools;
-use mark_last::MarkLastIterator;
-use std::io::{self, Write};
-use std::path::PathBuf;
-use structopt::{clap::ArgGroup, StructOpt};
+use charset::{Charset, Glyph, Nonprintable};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{mpsc, LazyLock};
+use std::thread;
+use structopt::{
+    clap::{AppSettings, ArgGroup},
+    StructOpt,
+};
+use thiserror::Error;
 
 #[derive(Debug, StructOpt)]
-#[structopt(name = "hex", about = "A hexdump utility.", author, group = ArgGroup::with_name("format").required(false).multiple(true))]
+#[structopt(name = "hex", about = "A hexdump utility.", author, group = ArgGroup::with_name("format").required(false).multiple(true), setting = AppSettings::ArgsNegateSubcommands)]
 pub struct Options {
     /// Enable one-byte octal display.
     #[structopt(short = "b", long = "one-byte-octal", group = "format")]
@@ -19,6 +47,9 @@ pub struct Options {
     /// Enable one-byte character display.
     #[structopt(short = "c", long = "one-byte-char", group = "format")]
     pub one_byte_char: bool,
+    /// Enable one-byte named character display, like `od -a`.
+    #[structopt(short = "a", long = "one-byte-named", group = "format")]
+    pub one_byte_named: bool,
     /// Enable two-byte octal display.
     #[structopt(short = "o", long = "two-bytes-octal", group = "format")]
     pub two_bytes_octal: bool,
@@ -31,182 +62,747 @@ pub struct Options {
     /// Enable two-byte decimal display.
     #[structopt(short = "d", long = "two-bytes-decimal", group = "format")]
     pub decimal: bool,
+    /// Display with a `hexdump -e` format string. May be given several times.
+    #[structopt(short = "e", long = "format", number_of_values = 1, group = "format")]
+    pub formats: Vec<String>,
+    /// Display with the `hexdump` format strings in this file, one per line.
+    #[structopt(
+        short = "f",
+        long = "format-file",
+        number_of_values = 1,
+        parse(from_os_str),
+        group = "format"
+    )]
+    pub format_files: Vec<PathBuf>,
+    /// Reproduce the output of `hexdump -C`, `xxd` or `od` byte for byte.
+    #[structopt(long = "compat", conflicts_with = "format")]
+    pub compat: Option<compat::Compat>,
+    /// Show `od`-style rows with one line per type: a, c, d, o, u or x, optionally
+    /// followed by a size in bytes or C, S, I or L, and by z for a text column.
+    /// May be given several times.
+    #[structopt(short = "t", long = "type", number_of_values = 1)]
+    pub od_types: Vec<od::TypeList>,
+    /// Radix of `od`-style offsets: d, o, x, or n for none.
+    #[structopt(short = "A", long = "address-radix")]
+    pub address_radix: Option<od::AddressRadix>,
+    /// Bytes per `od`-style row.
+    #[structopt(short = "w", long = "width")]
+    pub width: Option<usize>,
+    /// Same as `--skip`, as in `od`.
+    #[structopt(short = "j", parse(try_from_str = parse_bytes), conflicts_with = "skip")]
+    pub od_skip: Option<u128>,
+    /// Same as `--length`, as in `od`.
+    #[structopt(short = "N", parse(try_from_str = parse_bytes), conflicts_with = "length")]
+    pub od_length: Option<u128>,
+    /// How the built-in displays show offsets: hex, dec, oct, or none to hide them.
+    /// They grow wider for inputs over 4 GiB.
+    #[structopt(long = "offset-format", conflicts_with = "compat")]
+    pub offset_format: Option<OffsetFormat>,
+    /// Add BASE to every offset shown, such as the address an image is loaded at.
+    #[structopt(
+        long = "display-offset",
+        value_name = "BASE",
+        parse(try_from_str = formats::parse_address),
+        conflicts_with = "container-offsets"
+    )]
+    pub display_offset: Option<u64>,
+    /// Show offsets relative to `--skip` instead of to the start of the input.
+    #[structopt(long = "relative", conflicts_with = "container-offsets")]
+    pub relative: bool,
+    /// Start rows on multiples of the row size, as in a full dump, even when `--skip`
+    /// isn't one. The cells ahead of the input are left blank.
+    #[structopt(long = "align", conflicts_with = "follow")]
+    pub align: bool,
+    /// How the text of `-C` and `-c` is decoded: ascii, utf-8, utf-16le, utf-16be,
+    /// latin-1, ebcdic or cp437.
+    #[structopt(long = "charset", default_value = "ascii")]
+    pub charset: Charset,
+    /// How the text of `-C` shows control characters and bytes that don't decode:
+    /// dot, caret (`^J`), pictures (`␊`) or glyphs (`⋄ _ • ×`, like `hexyl`).
+    #[structopt(long = "nonprintable", default_value = "dot")]
+    pub nonprintable: Nonprintable,
+    /// Show offsets as a sector number and an offset into the sector, for sectors of
+    /// SIZE bytes such as 512, with a line where each sector starts.
+    #[structopt(
+        long = "sector",
+        value_name = "SIZE",
+        parse(try_from_str = parse_bytes),
+        conflicts_with_all = &["compat", "format", "offset-format", "od-types", "address-radix", "width"]
+    )]
+    pub sector: Option<u128>,
+    /// Show rows repeating the one before them, which `hexdump` and `od` layouts
+    /// otherwise replace with `*`.
+    #[structopt(short = "v", long = "no-squeeze")]
+    pub no_squeeze: bool,
 
+    /// Append a column with the entropy of each row.
+    #[structopt(long = "entropy")]
+    pub entropy: bool,
+    /// Number of bytes, ending at each row, the entropy column is computed over.
+    #[structopt(long = "entropy-window", default_value = "16", parse(try_from_str = parse_bytes))]
+    pub entropy_window: u128,
+
+    /// Decode the input with a binary template instead of dumping it.
+    #[structopt(long = "template", parse(from_os_str))]
+    pub template: Option<PathBuf>,
+
+    /// Dump this member of the tar or zip archive given as input.
+    /// An input of the form `ARCHIVE:MEMBER` does the same.
+    #[structopt(long = "member")]
+    pub member: Option<String>,
+    /// List the members of the tar or zip archive given as input.
+    #[structopt(long = "list-members")]
+    pub list_members: bool,
+    /// Show each row's offset in the archive after its offset in the member.
+    #[structopt(long = "container-offsets")]
+    pub container_offsets: bool,
+
+    /// Show only the end of the input: N bytes, or N rows when written as `Nrows`.
+    /// It starts on a row boundary, so rows line up with a full dump.
+    #[structopt(long = "tail", value_name = "N")]
+    pub tail: Option<Tail>,
+
+    /// Keep reading as the file grows, like `tail -f`, starting over from its beginning
+    /// should it be truncated or replaced.
+    #[structopt(
+        long = "follow",
+        conflicts_with_all = &["decompress", "member", "length", "template", "list-members"]
+    )]
+    pub follow: bool,
+
+    /// Read regular files through the buffered stream instead of mapping them into memory.
+    #[structopt(long = "no-mmap")]
+    pub no_mmap: bool,
+
+    /// Never print the detected file type above the dump. It is only shown on a terminal.
+    #[structopt(long = "no-banner")]
+    pub no_banner: bool,
+
+    /// Render memory-mapped input with this many threads, 0 meaning one per core.
+    /// Streams and `--entropy` dumps are always rendered by a single thread.
+    #[structopt(long = "threads", default_value = "1")]
+    pub threads: usize,
+
+    #[structopt(flatten)]
+    pub range: RangeOptions,
+
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    pub input: Option<PathBuf>,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Find runs of printable characters in the input.
+    Strings(strings::StringsOptions),
+    /// Show the byte distribution and entropy of the input.
+    Stats(stats::StatsOptions),
+    /// Compute checksums and digests of the input.
+    Hash(hash::HashOptions),
+    /// Decode ELF headers, or dump one section.
+    Elf(formats::elf::ElfOptions),
+    /// Decode PE/COFF headers, or dump one section or address.
+    Pe(formats::pe::PeOptions),
+    /// List the chunks of a PNG, JPEG or GIF image.
+    Image(formats::image::ImageOptions),
+    /// Inspect the records of a ZIP archive, or dump one entry's compressed data.
+    Zip(formats::zip::ZipOptions),
+    /// Guess the type of each input from its magic number.
+    Identify(magic::IdentifyOptions),
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub struct RangeOptions {
     /// Interpret only `length` bytes of input.
     #[structopt(short = "n", long = "length", parse(try_from_str = parse_bytes))]
     pub length: Option<u128>,
     /// Skip `offset` bytes from the beginning of the input.
     #[structopt(short = "s", long = "skip", parse(try_from_str = parse_bytes))]
     pub skip: Option<u128>,
+    /// Decompress gzip, zstd, xz or bzip2 input, detected by its magic number.
+    /// Offsets, `skip` and `length` then count decompressed bytes.
+    #[structopt(short = "z", long = "decompress")]
+    pub decompress: bool,
+}
 
-    /// Input file
-    #[structopt(parse(from_os_str))]
-    pub input: PathBuf,
+/// Open `path`, skipping and limiting it according to `range`. Returns the reader
+/// together with the offset of its first byte.
+pub fn open_range(path: &Path, range: &RangeOptions) -> io::Result<(Box<dyn io::Read>, u128)> {
+    let file = fs::File::open(path)?;
+
+    if range.decompress {
+        return stream_range(decompress::auto_decoder(io::BufReader::new(file))?, range);
+    }
+    // Pipes and character devices can't seek, so the bytes skipped are read and
+    // dropped. Block devices are read in aligned blocks.
+    let metadata = file.metadata()?;
+    if !device::is_seekable(&metadata) {
+        return stream_range(Box::new(io::BufReader::new(file)), range);
+    }
+    let offset = range.skip.unwrap_or(0);
+    let reader: Box<dyn io::Read> = if device::is_block_device(&metadata) {
+        Box::new(device::AlignedReader::new(
+            file,
+            offset.try_into().unwrap_or(u64::MAX),
+        )?)
+    } else {
+        let mut reader = io::BufReader::new(file);
+        if offset > 0 {
+            reader.seek(SeekFrom::Start(offset.try_into().unwrap()))?;
+        }
+        Box::new(reader)
+    };
+
+    if let Some(len) = range.length {
+        Ok((Box::new(reader.take(len.try_into().unwrap())), offset))
+    } else {
+        Ok((reader, offset))
+    }
+}
+
+/// Apply `range` to a reader that cannot seek, decoding and discarding up to `skip`.
+pub fn stream_range(
+    mut reader: Box<dyn io::Read>,
+    range: &RangeOptions,
+) -> io::Result<(Box<dyn io::Read>, u128)> {
+    let skip = range.skip.unwrap_or(0);
+    io::copy(
+        &mut (&mut reader).take(skip.try_into().unwrap_or(u64::MAX)),
+        &mut io::sink(),
+    )?;
+    if let Some(len) = range.length {
+        reader = Box::new(reader.take(len.try_into().unwrap_or(u64::MAX)));
+    }
+    Ok((reader, skip))
+}
+
+/// How much of the end of the input `--tail` shows.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Tail {
+    Bytes(u128),
+    Rows(u128),
+}
+
+impl FromStr for Tail {
+    type Err = byte_unit::ByteError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim_end();
+        match s.strip_suffix("rows").or_else(|| s.strip_suffix("row")) {
+            Some(rows) => Ok(Tail::Rows(parse_bytes(rows.trim_end())?)),
+            None => Ok(Tail::Bytes(parse_bytes(s)?)),
+        }
+    }
+}
+
+impl Tail {
+    /// How many of `len` bytes to leave out, keeping rows of `row_size` where a full dump
+    /// would have them. The first row starts `lead` bytes ahead of the input.
+    pub fn start(self, len: u128, row_size: usize, lead: usize) -> u128 {
+        let (len, row_size, lead) = (len + lead as u128, row_size as u128, lead as u128);
+        let start = match self {
+            Tail::Bytes(0) | Tail::Rows(0) => len,
+            Tail::Bytes(n) => len.saturating_sub(n) / row_size * row_size,
+            Tail::Rows(n) => len.div_ceil(row_size).saturating_sub(n) * row_size,
+        };
+        start.saturating_sub(lead)
+    }
+
+    /// The most bytes left by `start`, whatever the length of the input.
+    pub(crate) fn max_len(self, row_size: usize) -> u128 {
+        let row_size = row_size as u128;
+        match self {
+            Tail::Bytes(n) => n.saturating_add(row_size - 1),
+            Tail::Rows(n) => n.saturating_mul(row_size),
+        }
+    }
 }
 
 pub struct ChunkData<'a> {
     pub offset: u128,
     pub chunk: &'a [u8],
+    /// Blank cells before `chunk`, for a row starting on a boundary ahead of the input.
+    /// `offset` is that of the first blank cell.
+    pub pad: usize,
+}
+
+impl ChunkData<'_> {
+    /// The row's bytes, with zeros standing in for the blank cells.
+    pub(crate) fn padded(&self) -> Cow<'_, [u8]> {
+        if self.pad == 0 {
+            return Cow::Borrowed(self.chunk);
+        }
+        let mut row = vec![0; self.pad];
+        row.extend_from_slice(self.chunk);
+        Cow::Owned(row)
+    }
 }
 pub trait LineWriter {
     fn print_idx(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()> {
-        write!(w, "{:#010x}\t", data.offset)
+        render::write_offset(w, data.offset)
     }
 
     fn print_chunk(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()>;
+
+    /// Bytes per row. Writers shown together must agree on it.
+    fn row_size(&self) -> usize {
+        ROW_SIZE
+    }
+
+    /// Called once after the last row, with the offsets of the first byte and of the
+    /// end of the input.
+    fn print_end(&self, _start: u128, _end: u128, _w: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct CanonicalWriter {
+    charset: Charset,
+    nonprintable: Nonprintable,
+}
+
+impl CanonicalWriter {
+    pub fn new(charset: Charset, nonprintable: Nonprintable) -> Self {
+        CanonicalWriter {
+            charset,
+            nonprintable,
+        }
+    }
 }
 
-pub struct CanonicalWriter;
 impl LineWriter for CanonicalWriter {
     fn print_chunk(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()> {
-        let size = if data.chunk.len() > 8 {
-            let (first, second) = data.chunk.split_at(8);
-            for byte in first {
-                write!(w, "{:02X} ", byte)?;
-            }
-            write!(w, " ")?;
-            for (last, byte) in second.iter().mark_last() {
-                write!(w, "{:02X}", byte)?;
-                if !last {
-                    write!(w, " ")?;
-                }
-            }
-            data.chunk.len() * 3
-        } else {
-            for (last, byte) in data.chunk.iter().mark_last() {
-                write!(w, "{:02X}", byte)?;
-                if !last {
-                    write!(w, " ")?;
-                }
-            }
-            data.chunk.len() * 3 - 1
-        };
-        if data.chunk.len() < 16 {
-            let full = 16 * 3;
-            let remain = full - size;
-            for _i in 0..remain {
-                write!(w, " ")?;
+        // 48 columns of hex, with an extra space after the eighth byte, then the text.
+        const TEXT: usize = 16 * 3 + 2;
+        let pad = data.pad.min(16);
+        let chunk = &data.chunk[..data.chunk.len().min(16 - pad)];
+        let mut line = [b' '; TEXT + 16 + 2];
+        for (i, &byte) in (pad..).zip(chunk) {
+            let at = i * 3 + (i >= 8) as usize;
+            line[at..at + 2].copy_from_slice(&render::HEX_UPPER[byte as usize]);
+            line[TEXT + i] = render::PRINTABLE[byte as usize];
+        }
+        line[TEXT - 2] = b'\t';
+        line[TEXT - 1] = b'|';
+        if self.charset != Charset::Ascii || self.nonprintable != Nonprintable::Dot {
+            w.write_all(&line[..TEXT + pad])?;
+            let mut text = String::with_capacity(chunk.len() * 4 + 2);
+            for glyph in self.charset.decode(chunk) {
+                self.nonprintable.push(glyph, &mut text);
             }
+            text.push_str("|\n");
+            return w.write_all(text.as_bytes());
         }
+        let end = TEXT + pad + chunk.len();
+        line[end] = b'|';
+        line[end + 1] = b'\n';
+        w.write_all(&line[..end + 2])
+    }
+}
 
-        write!(w, "\t|")?;
-        for &byte in data.chunk {
-            if byte.is_ascii() {
-                let ch = byte as char;
-                if ch.is_control() {
-                    write!(w, ".")?;
-                } else {
-                    write!(w, "{}", ch)?;
-                }
-            } else {
-                write!(w, ".")?;
-            }
+/// Write `blank` empty cells and then `cells`, separated by single spaces and followed
+/// by a newline, in one call.
+fn write_cells<const N: usize>(
+    blank: usize,
+    cells: impl Iterator<Item = [u8; N]>,
+    w: &mut dyn Write,
+) -> io::Result<()> {
+    let mut line = [0u8; 16 * 7];
+    let mut len = 0;
+    for cell in iter::repeat_n([b' '; N], blank).chain(cells) {
+        if len > 0 {
+            line[len] = b' ';
+            len += 1;
         }
-        writeln!(w, "|")?;
-        Ok(())
+        line[len..len + N].copy_from_slice(&cell);
+        len += N;
     }
+    line[len] = b'\n';
+    w.write_all(&line[..len + 1])
 }
 
 pub struct OneByteOctal;
 impl LineWriter for OneByteOctal {
     fn print_chunk(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()> {
-        for (last, byte) in data.chunk.iter().mark_last() {
-            write!(w, "{:03o}", byte)?;
-            if !last {
-                write!(w, " ")?;
-            }
-        }
-        writeln!(w)?;
-        Ok(())
+        write_cells(
+            data.pad,
+            data.chunk.iter().map(|&b| render::OCTAL[b as usize]),
+            w,
+        )
+    }
+}
+
+/// Every byte as `render` shows it, right-aligned in three columns.
+fn cells(render: fn(u8, &mut [u8; 3]) -> &[u8]) -> [[u8; 3]; 256] {
+    let mut table = [[b' '; 3]; 256];
+    for (byte, cell) in (0..=255).zip(table.iter_mut()) {
+        let mut buf = [0; 3];
+        let text = render(byte, &mut buf);
+        cell[3 - text.len()..].copy_from_slice(text);
+    }
+    table
+}
+
+/// Cells of `OneByteChar`: printable characters, C escapes, or octal, as `od -c` shows them.
+static CHAR_CELLS: LazyLock<[[u8; 3]; 256]> = LazyLock::new(|| cells(od::escaped_char));
+
+/// Cells of `OneByteNamed`: printable characters or names, as `od -a` shows them.
+static NAMED_CELLS: LazyLock<[[u8; 3]; 256]> = LazyLock::new(|| cells(od::named_char));
+
+#[derive(Default)]
+pub struct OneByteChar {
+    charset: Charset,
+}
+
+impl OneByteChar {
+    pub fn new(charset: Charset) -> Self {
+        OneByteChar { charset }
     }
 }
 
-pub struct OneByteChar;
 impl LineWriter for OneByteChar {
     fn print_chunk(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()> {
-        for (last, byte) in data.chunk.iter().copied().mark_last() {
-            let escaped = match byte as char {
-                '\t' => "\\t".to_string(),
-                '\n' => "\\n".to_string(),
-                '\r' => "\\r".to_string(),
-                '\0' => "\\0".to_string(),
-                ch if (0x20..=0x7e).contains(&byte) => ch.to_string(),
-                _ => format!("{:o}", byte),
-            };
-            write!(w, "{:>03}", escaped)?;
-            if !last {
-                write!(w, " ")?;
+        if self.charset == Charset::Ascii {
+            return write_cells(
+                data.pad,
+                data.chunk.iter().map(|&b| CHAR_CELLS[b as usize]),
+                w,
+            );
+        }
+        // Characters right-aligned in the same three columns, continuation bytes as `**`.
+        let mut line = String::with_capacity((data.pad + data.chunk.len()) * 4 + 1);
+        for _ in 0..data.pad {
+            line.push_str("    ");
+        }
+        let glyphs = self.charset.decode(data.chunk);
+        for (i, (&byte, glyph)) in data.chunk.iter().zip(glyphs).enumerate() {
+            if i + data.pad > 0 {
+                line.push(' ');
             }
+            let cell = match glyph {
+                Glyph::Char(c) => [' ', ' ', c],
+                Glyph::Continuation => [' ', '*', '*'],
+                Glyph::Control(c) if c.is_ascii() => CHAR_CELLS[c as usize].map(char::from),
+                Glyph::Control(_) | Glyph::Invalid(_) => {
+                    render::OCTAL[byte as usize].map(char::from)
+                }
+            };
+            line.extend(cell);
         }
-        writeln!(w)?;
-        Ok(())
+        line.push('\n');
+        w.write_all(line.as_bytes())
     }
 }
 
+/// Bytes as `od -a` names them, such as `nul` and `del`, ignoring the high bit.
+pub struct OneByteNamed;
+impl LineWriter for OneByteNamed {
+    fn print_chunk(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()> {
+        write_cells(
+            data.pad,
+            data.chunk.iter().map(|&b| NAMED_CELLS[b as usize]),
+            w,
+        )
+    }
+}
+
+/// Native-endian halfwords of `chunk`, a trailing odd byte standing on its own.
+fn halfwords(chunk: &[u8]) -> impl Iterator<Item = u16> + '_ {
+    chunk.chunks(2).map(|pair| match *pair {
+        [x, y] => NativeEndian::read_u16(&[x, y]),
+        [x] => x as u16,
+        _ => unreachable!(),
+    })
+}
+
+/// Halfwords zero-padded to `N` digits in `radix`. Halfwords in the row's blank cells,
+/// even partly, are left blank.
+fn write_halfwords<const N: usize>(
+    data: &ChunkData<'_>,
+    radix: u64,
+    w: &mut dyn Write,
+) -> io::Result<()> {
+    let blank = data.pad.div_ceil(2);
+    let chunk = &data.chunk[(data.pad % 2).min(data.chunk.len())..];
+    let cells = halfwords(chunk).map(|halfword| {
+        let mut cell = [0u8; N];
+        render::put_digits(&mut cell, halfword.into(), radix);
+        cell
+    });
+    write_cells(blank, cells, w)
+}
+
 pub struct DecimalWriter;
 impl LineWriter for DecimalWriter {
     fn print_chunk(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()> {
-        let merged = data.chunk.iter().batching(|it| match it.next() {
-            None => None,
-            Some(x) => match it.next() {
-                None => Some(*x as u16),
-                Some(y) => Some(NativeEndian::read_u16(&[*x, *y])),
-            },
-        });
-        for (last, halfword) in merged.mark_last() {
-            write!(w, "{:05}", halfword)?;
-            if !last {
-                write!(w, " ")?;
-            }
-        }
-        writeln!(w)?;
-        Ok(())
+        write_halfwords::<5>(data, 10, w)
     }
 }
 
 pub struct TwoBytesOctal;
 impl LineWriter for TwoBytesOctal {
     fn print_chunk(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()> {
-        let merged = data.chunk.iter().batching(|it| match it.next() {
-            None => None,
-            Some(x) => match it.next() {
-                None => Some(*x as u16),
-                Some(y) => Some(NativeEndian::read_u16(&[*x, *y])),
-            },
-        });
-        for (last, halfword) in merged.mark_last() {
-            write!(w, "{:06o}", halfword)?;
-            if !last {
-                write!(w, " ")?;
-            }
-        }
-        writeln!(w)?;
-        Ok(())
+        write_halfwords::<6>(data, 8, w)
     }
 }
 
 pub struct TwoBytesHex;
 impl LineWriter for TwoBytesHex {
     fn print_chunk(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()> {
-        let merged = data.chunk.iter().batching(|it| match it.next() {
-            None => None,
-            Some(x) => match it.next() {
-                None => Some(*x as u16),
-                Some(y) => Some(NativeEndian::read_u16(&[*x, *y])),
-            },
-        });
-        for (last, halfword) in merged.mark_last() {
-            write!(w, "{:04X}", halfword)?;
-            if !last {
-                write!(w, " ")?;
-            }
+        write_halfwords::<4>(data, 16, w)
+    }
+}
+
+/// Decorates another `LineWriter` with a gutter showing the entropy of the
+/// `window` bytes ending at each row.
+pub struct EntropyGutter {
+    inner: Box<dyn LineWriter>,
+    window: usize,
+    history: RefCell<VecDeque<u8>>,
+    /// The inner writer's current row, reused between rows.
+    line: RefCell<Vec<u8>>,
+}
+
+impl EntropyGutter {
+    pub fn new(inner: Box<dyn LineWriter>, window: usize) -> Self {
+        EntropyGutter {
+            inner,
+            window: window.max(1),
+            history: RefCell::new(VecDeque::with_capacity(window)),
+            line: RefCell::new(vec![]),
+        }
+    }
+}
+
+impl LineWriter for EntropyGutter {
+    fn print_idx(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()> {
+        self.inner.print_idx(data, w)
+    }
+
+    fn print_chunk(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()> {
+        let mut history = self.history.borrow_mut();
+        history.extend(data.chunk);
+        while history.len() > self.window.max(data.chunk.len()) {
+            history.pop_front();
+        }
+
+        let mut line = self.line.borrow_mut();
+        line.clear();
+        self.inner.print_chunk(data, &mut *line)?;
+        // Rows the inner writer leaves out, like repeats it squeezes, get no gutter.
+        if line.is_empty() {
+            return Ok(());
+        }
+        let newline = line.last() == Some(&b'\n');
+        if newline {
+            line.pop();
+        }
+        w.write_all(&line)?;
+        let mut histogram = stats::Histogram::new();
+        let (front, back) = history.as_slices();
+        histogram.add(front);
+        histogram.add(back);
+
+        // A window of n bytes can't have more than log2(n) bits of entropy, so scale
+        // the glyph to what the window could reach rather than to 8 bits.
+        let value = histogram.entropy();
+        let max = (history.len().min(256) as f64).log2();
+        let fraction = if max > 0.0 { value / max } else { 0.0 };
+        write!(w, "\t{} {:.2}", stats::spark(fraction), value)?;
+
+        if newline {
+            writeln!(w)?;
         }
-        writeln!(w)?;
         Ok(())
     }
+
+    fn row_size(&self) -> usize {
+        self.inner.row_size()
+    }
+
+    fn print_end(&self, start: u128, end: u128, w: &mut dyn Write) -> io::Result<()> {
+        self.inner.print_end(start, end, w)
+    }
+}
+
+/// How the built-in writers show the offset of each row.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OffsetFormat {
+    Hex,
+    Dec,
+    Oct,
+    None,
+}
+
+#[derive(Debug, Clone, Error)]
+#[error("unknown offset format {0:?}; expected hex, dec, oct or none")]
+pub struct UnknownOffsetFormatError(String);
+
+impl FromStr for OffsetFormat {
+    type Err = UnknownOffsetFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hex" => Ok(OffsetFormat::Hex),
+            "dec" => Ok(OffsetFormat::Dec),
+            "oct" => Ok(OffsetFormat::Oct),
+            "none" => Ok(OffsetFormat::None),
+            _ => Err(UnknownOffsetFormatError(s.to_string())),
+        }
+    }
+}
+
+impl OffsetFormat {
+    /// Digits shown for offsets up to `max`: enough for any 32-bit offset, and more
+    /// when the input goes beyond 4 GiB.
+    pub fn width(self, max: u128) -> usize {
+        let (radix, min) = match self {
+            OffsetFormat::Hex => (16, 8),
+            OffsetFormat::Dec => (10, 10),
+            OffsetFormat::Oct => (8, 11),
+            OffsetFormat::None => return 0,
+        };
+        let mut digits = 1;
+        let mut rest = max / radix;
+        while rest > 0 {
+            digits += 1;
+            rest /= radix;
+        }
+        digits.max(min)
+    }
+}
+
+/// Replaces the offset column of a built-in writer with one in another radix or
+/// width, or hides it.
+pub struct OffsetColumn {
+    inner: Box<dyn LineWriter>,
+    format: OffsetFormat,
+    width: usize,
+}
+
+impl OffsetColumn {
+    pub fn new(inner: Box<dyn LineWriter>, format: OffsetFormat, width: usize) -> Self {
+        OffsetColumn {
+            inner,
+            format,
+            width,
+        }
+    }
+}
+
+impl LineWriter for OffsetColumn {
+    fn print_idx(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()> {
+        let width = self.width;
+        match self.format {
+            OffsetFormat::Hex if width == 8 => render::write_offset(w, data.offset),
+            OffsetFormat::Hex => write!(w, "0x{:01$x}\t", data.offset, width),
+            OffsetFormat::Dec => write!(w, "{:01$}\t", data.offset, width),
+            OffsetFormat::Oct => write!(w, "{:01$o}\t", data.offset, width),
+            OffsetFormat::None => Ok(()),
+        }
+    }
+
+    fn print_chunk(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()> {
+        self.inner.print_chunk(data, w)
+    }
+
+    fn row_size(&self) -> usize {
+        self.inner.row_size()
+    }
+
+    fn print_end(&self, start: u128, end: u128, w: &mut dyn Write) -> io::Result<()> {
+        self.inner.print_end(start, end, w)
+    }
+}
+
+/// Replaces the offset column of a built-in writer with the number of the sector the
+/// row is in and the offset into it, as `sector:offset`. With `separators`, a line marks
+/// the start of each sector.
+pub struct SectorColumn {
+    inner: Box<dyn LineWriter>,
+    sector: u128,
+    /// Hex digits of the offsets into a sector.
+    width: usize,
+    separators: bool,
+}
+
+impl SectorColumn {
+    pub fn new(inner: Box<dyn LineWriter>, sector: u128, separators: bool) -> Self {
+        SectorColumn {
+            inner,
+            sector,
+            width: format!("{:x}", sector - 1).len(),
+            separators,
+        }
+    }
+}
+
+impl LineWriter for SectorColumn {
+    fn print_idx(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()> {
+        let sector = self.sector;
+        let end = data.offset + (data.pad + data.chunk.len()) as u128;
+        let boundary = data.offset.div_ceil(sector) * sector;
+        if self.separators && boundary < end {
+            writeln!(w, "--- sector {} ---", boundary / sector)?;
+        }
+        write!(
+            w,
+            "{:010}:{:02$x}\t",
+            data.offset / sector,
+            data.offset % sector,
+            self.width
+        )
+    }
+
+    fn print_chunk(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()> {
+        self.inner.print_chunk(data, w)
+    }
+
+    fn row_size(&self) -> usize {
+        self.inner.row_size()
+    }
+
+    fn print_end(&self, start: u128, end: u128, w: &mut dyn Write) -> io::Result<()> {
+        self.inner.print_end(start, end, w)
+    }
+}
+
+/// Adds each row's offset in an enclosing container after the writer's own offset.
+pub struct ContainerOffset {
+    inner: Box<dyn LineWriter>,
+    base: u128,
 }
 
-fn read_to_fill(reader: &mut dyn io::Read, buf: &mut [u8]) -> io::Result<usize> {
+impl ContainerOffset {
+    pub fn new(inner: Box<dyn LineWriter>, base: u128) -> Self {
+        ContainerOffset { inner, base }
+    }
+}
+
+impl LineWriter for ContainerOffset {
+    fn print_idx(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()> {
+        self.inner.print_idx(data, w)?;
+        write!(w, "@{:#010x}\t", self.base + data.offset)
+    }
+
+    fn print_chunk(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()> {
+        self.inner.print_chunk(data, w)
+    }
+
+    fn row_size(&self) -> usize {
+        self.inner.row_size()
+    }
+
+    fn print_end(&self, start: u128, end: u128, w: &mut dyn Write) -> io::Result<()> {
+        self.inner.print_end(start, end, w)
+    }
+}
+
+pub(crate) fn read_to_fill(reader: &mut dyn io::Read, buf: &mut [u8]) -> io::Result<usize> {
     let mut read = 0;
     loop {
         let buf = &mut buf[read..];
@@ -217,6 +813,8 @@ fn read_to_fill(reader: &mut dyn io::Read, buf: &mut [u8]) -> io::Result<usize>
                 read += n;
             }
             Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            // Hand back what was read; a persistent error recurs on the next call.
+            Err(_) if read > 0 => return Ok(read),
             Err(e) => return Err(e),
         }
         if read == buf.len() {
@@ -225,33 +823,196 @@ fn read_to_fill(reader: &mut dyn io::Read, buf: &mut [u8]) -> io::Result<usize>
     }
 }
 
+/// Bytes shown per row.
+const ROW_SIZE: usize = 16;
+
+fn print_row(
+    writers: &[Box<dyn LineWriter>],
+    chunk_data: &ChunkData<'_>,
+    w: &mut dyn Write,
+) -> io::Result<()> {
+    for writer in writers.iter() {
+        writer.print_idx(chunk_data, w)?;
+        writer.print_chunk(chunk_data, w)?;
+    }
+    Ok(())
+}
+
 pub fn print_lines(
     writers: &[Box<dyn LineWriter>],
     offset: u128,
     reader: &mut dyn io::Read,
 ) -> io::Result<()> {
-    const BUFFER_SIZE: usize = 16;
-    let mut buffer = [0u8; BUFFER_SIZE];
-    let mut idx = 0;
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::with_capacity(OUTPUT_BUFFER, stdout.lock());
+    print_lines_to(writers, offset, reader, &mut out)?;
+    out.flush()
+}
+
+/// Size of the buffer between the writers and standard output.
+pub const OUTPUT_BUFFER: usize = 64 << 10;
+/// Bytes read from a stream, or handed to a rendering thread, at a time.
+const BLOCK_SIZE: usize = 256 << 10;
+
+/// Like `print_lines`, writing to `w` instead of standard output.
+pub fn print_lines_to(
+    writers: &[Box<dyn LineWriter>],
+    offset: u128,
+    reader: &mut dyn io::Read,
+    w: &mut dyn Write,
+) -> io::Result<()> {
+    let row_size = row_size(writers);
+    let mut buffer = vec![0u8; BLOCK_SIZE.max(row_size)];
+    let mut filled = 0;
+    let mut position = offset;
     loop {
-        let amt = read_to_fill(reader, &mut buffer)?;
+        let amt = match reader.read(&mut buffer[filled..]) {
+            Ok(amt) => amt,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                // Show what was read, a partial row included, before the error.
+                print_rows(writers, position, &buffer[..filled], w)?;
+                return Err(e);
+            }
+        };
         if amt == 0 {
-            return Ok(());
+            print_rows(writers, position, &buffer[..filled], w)?;
+            return print_end(writers, offset, position + filled as u128, w);
         }
+        filled += amt;
 
+        // Render whole rows as soon as they arrive, keeping a partial row for later.
+        let rows = filled - filled % row_size;
+        print_rows(writers, position, &buffer[..rows], w)?;
+        position += rows as u128;
+        buffer.copy_within(rows..filled, 0);
+        filled -= rows;
+    }
+}
+
+/// Like `print_lines_to`, rendering rows straight from `data` without copying it.
+pub fn print_slice_to(
+    writers: &[Box<dyn LineWriter>],
+    offset: u128,
+    data: &[u8],
+    w: &mut dyn Write,
+) -> io::Result<()> {
+    print_rows(writers, offset, data, w)?;
+    print_end(writers, offset, offset + data.len() as u128, w)
+}
+
+/// Show the input up to the first row boundary after `offset` as one row starting on
+/// the boundary before it, with blank cells for the bytes ahead of the input. Returns
+/// how many bytes were shown, after which rows start on boundaries.
+pub fn print_aligned_head(
+    writers: &[Box<dyn LineWriter>],
+    offset: u128,
+    reader: &mut dyn io::Read,
+    w: &mut dyn Write,
+) -> io::Result<usize> {
+    let row_size = row_size(writers);
+    let pad = (offset % row_size as u128) as usize;
+    if pad == 0 {
+        return Ok(0);
+    }
+    let mut head = vec![0u8; row_size - pad];
+    let len = read_to_fill(reader, &mut head)?;
+    if len > 0 {
         let chunk_data = ChunkData {
-            offset: offset + (idx * BUFFER_SIZE as u128),
-            chunk: &buffer[..amt],
+            offset: offset - pad as u128,
+            chunk: &head[..len],
+            pad,
         };
+        print_row(writers, &chunk_data, w)?;
+    }
+    Ok(len)
+}
 
-        let mut stdout = std::io::stdout();
-        for writer in writers.iter() {
-            writer.print_idx(&chunk_data, &mut stdout)?;
-            writer.print_chunk(&chunk_data, &mut stdout)?;
-        }
+/// The row size of `writers`, which must all agree on it.
+pub(crate) fn row_size(writers: &[Box<dyn LineWriter>]) -> usize {
+    writers.first().map_or(ROW_SIZE, |writer| writer.row_size())
+}
+
+/// Render `data` as rows, leaving out what writers show after the last one.
+pub(crate) fn print_rows(
+    writers: &[Box<dyn LineWriter>],
+    offset: u128,
+    data: &[u8],
+    w: &mut dyn Write,
+) -> io::Result<()> {
+    let row_size = row_size(writers);
+    for (idx, chunk) in data.chunks(row_size).enumerate() {
+        let chunk_data = ChunkData {
+            offset: offset + (idx * row_size) as u128,
+            chunk,
+            pad: 0,
+        };
+        print_row(writers, &chunk_data, w)?;
+    }
+    Ok(())
+}
 
-        idx += 1;
+fn print_end(
+    writers: &[Box<dyn LineWriter>],
+    start: u128,
+    end: u128,
+    w: &mut dyn Write,
+) -> io::Result<()> {
+    for writer in writers {
+        writer.print_end(start, end, w)?;
     }
+    Ok(())
+}
+
+/// Like `print_slice_to`, splitting `data` into blocks rendered by `threads` threads
+/// and written in order. Every thread gets its own writers from `make_writers`, so
+/// writers that keep state across rows, like `EntropyGutter`, must not be used here.
+pub fn print_slice_parallel(
+    make_writers: &(dyn Fn() -> Vec<Box<dyn LineWriter>> + Sync),
+    offset: u128,
+    data: &[u8],
+    threads: usize,
+    w: &mut dyn Write,
+) -> io::Result<()> {
+    let writers = make_writers();
+    let row_size = row_size(&writers);
+    let block_size = BLOCK_SIZE.max(row_size) / row_size * row_size;
+    let blocks: Vec<&[u8]> = data.chunks(block_size).collect();
+    let threads = threads.min(blocks.len());
+    if threads <= 1 {
+        return print_slice_to(&writers, offset, data, w);
+    }
+
+    let blocks = &blocks;
+    thread::scope(|scope| {
+        // Thread `t` renders blocks t, t + threads, ..., so block i is read from the
+        // channel of thread i % threads. A dropped receiver stops its thread.
+        let receivers: Vec<_> = (0..threads)
+            .map(|t| {
+                let (tx, rx) = mpsc::sync_channel::<io::Result<Vec<u8>>>(1);
+                scope.spawn(move || {
+                    let writers = make_writers();
+                    for i in (t..blocks.len()).step_by(threads) {
+                        let mut out = Vec::with_capacity(blocks[i].len() * 5);
+                        let block_offset = offset + (i * block_size) as u128;
+                        let rendered = print_rows(&writers, block_offset, blocks[i], &mut out);
+                        if tx.send(rendered.map(|()| out)).is_err() {
+                            return;
+                        }
+                    }
+                });
+                rx
+            })
+            .collect();
+
+        for i in 0..blocks.len() {
+            let out = receivers[i % threads]
+                .recv()
+                .expect("rendering thread exited early")?;
+            w.write_all(&out)?;
+        }
+        print_end(&writers, offset, offset + data.len() as u128, w)
+    })
 }
 
 #[cfg(test)]
@@ -265,12 +1026,13 @@ mod tests {
     const TEST_CHUNK: ChunkData = ChunkData {
         offset: 16,
         chunk: &TEST_DATA,
+        pad: 0,
     };
 
     #[test]
     fn canonical() {
         let mut out = vec![];
-        let w = CanonicalWriter;
+        let w = CanonicalWriter::default();
         w.print_idx(&TEST_CHUNK, &mut out).unwrap();
         w.print_chunk(&TEST_CHUNK, &mut out).unwrap();
         assert_eq!(
@@ -294,12 +1056,67 @@ mod tests {
     #[test]
     fn one_byte_char() {
         let mut out = vec![];
-        let w = OneByteChar;
+        let w = OneByteChar::default();
         w.print_idx(&TEST_CHUNK, &mut out).unwrap();
         w.print_chunk(&TEST_CHUNK, &mut out).unwrap();
         assert_eq!(
             out,
-            b"0x00000010\t  T   h   i   s   1   i   s       a       c   h   u   n   k 377\n"
+            b"0x00000010\t  T   h   i   s 001   i   s       a       c   h   u   n   k 377\n"
+        );
+    }
+
+    #[test]
+    fn one_byte_char_matches_od() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        let golden = [
+            (
+                Box::new(OneByteChar::default()) as Box<dyn LineWriter>,
+                include_str!("../tests/fixtures/compat/bytes.od-c"),
+            ),
+            (
+                Box::new(OneByteNamed),
+                include_str!("../tests/fixtures/compat/bytes.od-a"),
+            ),
+        ];
+        for (w, expected) in &golden {
+            let mut out = String::new();
+            for chunk in bytes.chunks(16) {
+                let mut line = vec![];
+                w.print_chunk(
+                    &ChunkData {
+                        offset: 0,
+                        chunk,
+                        pad: 0,
+                    },
+                    &mut line,
+                )
+                .unwrap();
+                // `od` puts a space before every field rather than between them.
+                out.push(' ');
+                out.push_str(std::str::from_utf8(&line).unwrap());
+            }
+            assert_eq!(out, *expected);
+        }
+    }
+
+    #[test]
+    fn charsets() {
+        let data = ChunkData {
+            offset: 0,
+            chunk: "Grüße\u{1}\n".as_bytes(),
+            pad: 0,
+        };
+        let mut out = vec![];
+        CanonicalWriter::new(Charset::Utf8, Nonprintable::Dot)
+            .print_chunk(&data, &mut out)
+            .unwrap();
+        OneByteChar::new(Charset::Utf8)
+            .print_chunk(&data, &mut out)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "47 72 C3 BC C3 9F 65 01  0A                     \t|Grü·ß·e..|\n  \
+             G   r   ü  **   ß  **   e 001  \\n\n"
         );
     }
 
@@ -338,4 +1155,107 @@ mod tests {
             b"0x00000010\t6854 7369 6901 2073 2061 6863 6E75 FF6B\n"
         );
     }
+
+    #[test]
+    fn entropy_gutter() {
+        let mut out = vec![];
+        let w = EntropyGutter::new(Box::new(CanonicalWriter::default()), 16);
+        w.print_idx(&TEST_CHUNK, &mut out).unwrap();
+        w.print_chunk(&TEST_CHUNK, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "0x00000010\t54 68 69 73 01 69 73 20  61 20 63 68 75 6E 6B FF\t|This.is a chunk.|\t▇ 3.50\n"
+        );
+    }
+
+    #[test]
+    fn aligned_head() {
+        let make_writers = || -> Vec<Box<dyn LineWriter>> {
+            vec![
+                Box::new(CanonicalWriter::default()),
+                Box::new(OneByteOctal),
+                Box::new(TwoBytesHex),
+            ]
+        };
+        let mut out = vec![];
+        let mut reader = &TEST_DATA[..];
+        let shown = print_aligned_head(&make_writers(), 0x1b, &mut reader, &mut out).unwrap();
+        assert_eq!(shown, 5);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "0x00000010\t                                  54 68 69 73 01\t|           This.|\n\
+             0x00000010\t                                            124 150 151 163 001\n\
+             0x00000010\t                              6968 0173\n"
+        );
+
+        // Rows starting on a boundary are left alone.
+        let mut reader = &TEST_DATA[..];
+        assert_eq!(
+            print_aligned_head(&make_writers(), 0x20, &mut reader, &mut vec![]).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn sector_column() {
+        let show = |offset: u128, separators: bool| {
+            let mut out = vec![];
+            let w = SectorColumn::new(Box::new(TwoBytesHex), 512, separators);
+            let data = ChunkData {
+                offset,
+                ..TEST_CHUNK
+            };
+            w.print_idx(&data, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(show(0x200, true), "--- sector 1 ---\n0000000001:000\t");
+        assert_eq!(show(0x200, false), "0000000001:000\t");
+        assert_eq!(show(0x5f0, true), "0000000002:1f0\t");
+        // A row across a sector boundary is marked too.
+        assert_eq!(show(0x5f8, true), "--- sector 3 ---\n0000000002:1f8\t");
+    }
+
+    #[test]
+    fn tail() {
+        assert_eq!("100".parse::<Tail>().unwrap(), Tail::Bytes(100));
+        assert_eq!("2KiB".parse::<Tail>().unwrap(), Tail::Bytes(2048));
+        assert_eq!("3rows".parse::<Tail>().unwrap(), Tail::Rows(3));
+        assert_eq!("1 row".parse::<Tail>().unwrap(), Tail::Rows(1));
+        assert!("rows".parse::<Tail>().is_err());
+
+        // 100 bytes make six full rows and one of four bytes.
+        assert_eq!(Tail::Bytes(10).start(100, 16, 0), 80);
+        assert_eq!(Tail::Bytes(4).start(100, 16, 0), 96);
+        assert_eq!(Tail::Bytes(1000).start(100, 16, 0), 0);
+        assert_eq!(Tail::Rows(2).start(100, 16, 0), 80);
+        assert_eq!(Tail::Rows(7).start(100, 16, 0), 0);
+        // Nothing at all, not even the last partial row.
+        assert_eq!(Tail::Bytes(0).start(100, 16, 0), 100);
+        assert_eq!(Tail::Rows(0).start(100, 16, 0), 100);
+        assert_eq!(Tail::Rows(0).start(100, 16, 5), 100);
+        assert_eq!(Tail::Rows(0).start(0, 16, 0), 0);
+        // With the input starting 5 bytes into a row, rows start at 11, 27, ...
+        assert_eq!(Tail::Bytes(10).start(100, 16, 5), 75);
+        assert_eq!(Tail::Rows(7).start(100, 16, 5), 0);
+    }
+
+    #[test]
+    fn offset_column() {
+        assert_eq!(OffsetFormat::Hex.width(0xffff_ffff), 8);
+        assert_eq!(OffsetFormat::Hex.width(0x1_0000_0000), 9);
+        assert_eq!(OffsetFormat::Dec.width(5_000_000_000), 10);
+        assert_eq!(OffsetFormat::Oct.width(1 << 33), 12);
+
+        let show = |format: OffsetFormat, width: usize| {
+            let mut out = vec![];
+            let w = OffsetColumn::new(Box::new(TwoBytesHex), format, width);
+            w.print_idx(&TEST_CHUNK, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(show(OffsetFormat::Hex, 8), "0x00000010\t");
+        assert_eq!(show(OffsetFormat::Hex, 9), "0x000000010\t");
+        assert_eq!(show(OffsetFormat::Dec, 10), "0000000016\t");
+        assert_eq!(show(OffsetFormat::Oct, 11), "00000000020\t");
+        assert_eq!(show(OffsetFormat::None, 0), "");
+    }
 }
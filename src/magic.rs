@@ -0,0 +1,257 @@
+//! File type detection from leading magic numbers.
+
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct IdentifyOptions {
+    /// Input files
+    #[structopt(parse(from_os_str), required = true)]
+    pub inputs: Vec<PathBuf>,
+}
+
+/// A magic number at a fixed offset, optionally refined by a further check.
+pub struct Signature {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub offset: usize,
+    pub magic: &'static [u8],
+    check: Option<fn(&[u8]) -> bool>,
+}
+
+/// A signature found in the input.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub offset: usize,
+    pub len: usize,
+}
+
+const fn sig(
+    name: &'static str,
+    description: &'static str,
+    offset: usize,
+    magic: &'static [u8],
+) -> Signature {
+    Signature {
+        name,
+        description,
+        offset,
+        magic,
+        check: None,
+    }
+}
+
+const fn checked(
+    name: &'static str,
+    description: &'static str,
+    offset: usize,
+    magic: &'static [u8],
+    check: fn(&[u8]) -> bool,
+) -> Signature {
+    Signature {
+        name,
+        description,
+        offset,
+        magic,
+        check: Some(check),
+    }
+}
+
+fn u32_le(data: &[u8], at: usize) -> Option<u32> {
+    let bytes = data.get(at..at + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn u32_be(data: &[u8], at: usize) -> Option<u32> {
+    let bytes = data.get(at..at + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// An MZ header whose `e_lfanew` points at a PE signature within the peeked bytes.
+fn is_pe(data: &[u8]) -> bool {
+    u32_le(data, 0x3c)
+        .and_then(|at| data.get(at as usize..at as usize + 4))
+        .is_some_and(|sig| sig == b"PE\0\0")
+}
+
+fn is_dos(data: &[u8]) -> bool {
+    !is_pe(data)
+}
+
+// Java class files and fat Mach-O binaries share 0xCAFEBABE. The next word is the
+// class file version (45 and up) or the architecture count (small).
+fn is_class(data: &[u8]) -> bool {
+    u32_be(data, 4).is_some_and(|v| (v & 0xffff) >= 45)
+}
+
+fn is_fat_mach_o(data: &[u8]) -> bool {
+    u32_be(data, 4).is_some_and(|n| n > 0 && n < 45)
+}
+
+fn is_riff(data: &[u8]) -> bool {
+    data.starts_with(b"RIFF")
+}
+
+/// The built-in signature database, most specific first.
+pub const SIGNATURES: &[Signature] = &[
+    sig("elf", "ELF executable or object", 0, b"\x7fELF"),
+    checked("pe", "PE/COFF executable (Windows)", 0, b"MZ", is_pe),
+    checked("dos", "MS-DOS executable", 0, b"MZ", is_dos),
+    sig("mach-o", "Mach-O binary (32-bit)", 0, b"\xfe\xed\xfa\xce"),
+    sig("mach-o", "Mach-O binary (64-bit)", 0, b"\xfe\xed\xfa\xcf"),
+    sig(
+        "mach-o",
+        "Mach-O binary (32-bit, little endian)",
+        0,
+        b"\xce\xfa\xed\xfe",
+    ),
+    sig(
+        "mach-o",
+        "Mach-O binary (64-bit, little endian)",
+        0,
+        b"\xcf\xfa\xed\xfe",
+    ),
+    checked(
+        "mach-o",
+        "Mach-O universal binary",
+        0,
+        b"\xca\xfe\xba\xbe",
+        is_fat_mach_o,
+    ),
+    checked("class", "Java class file", 0, b"\xca\xfe\xba\xbe", is_class),
+    sig("dex", "Android Dalvik executable", 0, b"dex\n"),
+    sig("wasm", "WebAssembly binary module", 0, b"\0asm"),
+    sig("ar", "Unix ar archive", 0, b"!<arch>\n"),
+    sig("png", "PNG image", 0, b"\x89PNG\r\n\x1a\n"),
+    sig("jpeg", "JPEG image", 0, b"\xff\xd8\xff"),
+    sig("gif", "GIF image", 0, b"GIF87a"),
+    sig("gif", "GIF image", 0, b"GIF89a"),
+    checked("webp", "WebP image", 8, b"WEBP", is_riff),
+    checked("wav", "WAVE audio", 8, b"WAVE", is_riff),
+    sig("ogg", "Ogg container", 0, b"OggS"),
+    sig("flac", "FLAC audio", 0, b"fLaC"),
+    sig("pdf", "PDF document", 0, b"%PDF-"),
+    sig("gzip", "gzip compressed data", 0, b"\x1f\x8b"),
+    sig("zstd", "Zstandard compressed data", 0, b"\x28\xb5\x2f\xfd"),
+    sig("xz", "xz compressed data", 0, b"\xfd7zXZ\0"),
+    sig("bzip2", "bzip2 compressed data", 0, b"BZh"),
+    sig("lz4", "LZ4 frame", 0, b"\x04\x22\x4d\x18"),
+    sig("zip", "ZIP archive", 0, b"PK\x03\x04"),
+    sig("zip", "ZIP archive (empty)", 0, b"PK\x05\x06"),
+    sig("7z", "7-Zip archive", 0, b"7z\xbc\xaf\x27\x1c"),
+    sig("rar", "RAR archive", 0, b"Rar!\x1a\x07"),
+    sig("tar", "POSIX tar archive", 257, b"ustar"),
+    sig("sqlite", "SQLite 3 database", 0, b"SQLite format 3\0"),
+    sig("script", "script with interpreter line", 0, b"#!"),
+];
+
+/// How many leading bytes `identify` may look at.
+pub const PEEK_LEN: usize = 512;
+
+/// Every signature in the database that matches the start of `data`.
+pub fn identify(data: &[u8]) -> Vec<Match> {
+    SIGNATURES
+        .iter()
+        .filter(|s| {
+            data.get(s.offset..s.offset + s.magic.len()) == Some(s.magic)
+                && s.check.is_none_or(|check| check(data))
+        })
+        .map(|s| Match {
+            name: s.name,
+            description: s.description,
+            offset: s.offset,
+            len: s.magic.len(),
+        })
+        .collect()
+}
+
+/// Read up to `PEEK_LEN` bytes from `reader` and identify them.
+pub fn identify_reader(reader: &mut dyn Read) -> io::Result<Vec<Match>> {
+    let mut data = vec![];
+    reader.take(PEEK_LEN as u64).read_to_end(&mut data)?;
+    Ok(identify(&data))
+}
+
+/// The one-line summary used as a banner above dumps, if anything matched.
+pub fn banner(matches: &[Match]) -> Option<String> {
+    let first = matches.first()?;
+    Some(format!("# {} ({})", first.description, first.name))
+}
+
+pub fn print_identify(opt: &IdentifyOptions, w: &mut dyn Write) -> io::Result<()> {
+    let width = opt
+        .inputs
+        .iter()
+        .map(|p| p.display().to_string().len())
+        .max()
+        .unwrap_or(0);
+    for input in &opt.inputs {
+        let matches = identify_reader(&mut std::fs::File::open(input)?)?;
+        let name = format!("{}:", input.display());
+        match matches.first() {
+            Some(m) => writeln!(
+                w,
+                "{:<width$}  {}\t{} (magic at {:#x})",
+                name,
+                m.name,
+                m.description,
+                m.offset,
+                width = width + 1
+            )?,
+            None => writeln!(w, "{:<width$}  unknown", name, width = width + 1)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(data: &[u8]) -> Vec<&'static str> {
+        identify(data).iter().map(|m| m.name).collect()
+    }
+
+    #[test]
+    fn identify_common_formats() {
+        assert_eq!(names(b"\x7fELF\x02\x01\x01"), vec!["elf"]);
+        assert_eq!(names(b"\x1f\x8b\x08\0"), vec!["gzip"]);
+        assert_eq!(names(b"SQLite format 3\0\x10\0"), vec!["sqlite"]);
+        assert_eq!(names(b"RIFF\0\0\0\0WEBPVP8 "), vec!["webp"]);
+        assert_eq!(names(b"plain text"), Vec::<&str>::new());
+        assert_eq!(names(b"RIFX\0\0\0\0WEBP"), Vec::<&str>::new());
+
+        let mut tar = vec![0u8; 512];
+        tar[257..263].copy_from_slice(b"ustar\0");
+        assert_eq!(names(&tar), vec!["tar"]);
+    }
+
+    #[test]
+    fn refine_shared_magic() {
+        let mut pe = vec![0u8; 0x48];
+        pe[..2].copy_from_slice(b"MZ");
+        pe[0x3c] = 0x40;
+        pe[0x40..0x44].copy_from_slice(b"PE\0\0");
+        assert_eq!(names(&pe), vec!["pe"]);
+        pe[0x40] = 0;
+        assert_eq!(names(&pe), vec!["dos"]);
+
+        assert_eq!(names(b"\xca\xfe\xba\xbe\0\0\0\x34"), vec!["class"]);
+        assert_eq!(names(b"\xca\xfe\xba\xbe\0\0\0\x02"), vec!["mach-o"]);
+
+        let matches = identify(b"\x89PNG\r\n\x1a\n");
+        assert_eq!(banner(&matches).unwrap(), "# PNG image (png)");
+        assert_eq!(
+            matches[0],
+            Match {
+                name: "png",
+                description: "PNG image",
+                offset: 0,
+                len: 8
+            }
+        );
+    }
+}
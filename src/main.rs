@@ -1,47 +1,252 @@
-use hex::LineWriter;
+use hex::compat::Compat;
+use hex::format_string::{FormatStringError, FormatWriter};
+use hex::input::Input;
+use hex::od::{AddressRadix, OdType};
+use hex::{LineWriter, OffsetFormat};
 use human_panic::setup_panic;
 use std::convert::TryInto;
-use std::io::prelude::*;
-use std::{
-    fs,
-    io::{self, SeekFrom},
-};
-use structopt::StructOpt;
+use std::fmt::Display;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::{fs, io, process};
+use structopt::{clap, StructOpt};
+
+/// Report a decoding error against the file it came from and exit.
+fn fail(path: &Path, error: impl Display) -> ! {
+    eprintln!("{}: {}", path.display(), error);
+    process::exit(1);
+}
+
+/// Report errors about the input's contents, such as a corrupt compressed stream,
+/// like decode errors rather than as I/O failures.
+fn check_input<T>(path: &Path, result: io::Result<T>) -> io::Result<T> {
+    match result {
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof
+            ) =>
+        {
+            fail(path, e)
+        }
+        result => result,
+    }
+}
+
+/// Report a format string error with the offending part of `source` and exit.
+fn fail_format(path: &Path, source: &str, error: FormatStringError) -> ! {
+    match error.context(source) {
+        Some(context) => fail(path, format!("{}\n{}", error, context)),
+        None => fail(path, error),
+    }
+}
+
+/// Compile the `-e` and `-f` format strings into one writer, if any were given.
+fn format_writer(opt: &hex::Options) -> io::Result<Option<FormatWriter>> {
+    if opt.formats.is_empty() && opt.format_files.is_empty() {
+        return Ok(None);
+    }
+    let mut strings = vec![];
+    for source in &opt.formats {
+        match hex::format_string::parse(source) {
+            Ok(string) => strings.push(string),
+            Err(e) => fail_format(Path::new("-e"), source, e),
+        }
+    }
+    for path in &opt.format_files {
+        let source = fs::read_to_string(path)?;
+        match hex::format_string::parse_file(&source) {
+            Ok(more) => strings.extend(more),
+            Err(e) => fail_format(path, &source, e),
+        }
+    }
+    match FormatWriter::new(strings) {
+        Ok(writer) => Ok(Some(writer)),
+        Err(e) => fail(Path::new("-e"), e),
+    }
+}
 
 fn main() -> io::Result<()> {
-    setup_panic!();
+    // human-panic still names the deprecated `PanicInfo` alias.
+    #[allow(deprecated)]
+    {
+        setup_panic!();
+    }
 
     let mut opt = hex::Options::from_args();
-    if !opt.one_byte_octal
-        && !opt.one_byte_char
-        && !opt.two_bytes_octal
-        && !opt.two_bytes_hex
-        && !opt.canonical
-        && !opt.decimal
-    {
-        opt.canonical = true;
+    match opt.command {
+        Some(hex::Command::Strings(ref strings)) => {
+            let file = fs::File::open(&strings.input)?;
+            let mut reader = io::BufReader::new(file);
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            return hex::strings::print_strings(strings, &mut reader, &mut stdout);
+        }
+        Some(hex::Command::Stats(ref stats)) => {
+            let (mut reader, offset) =
+                check_input(&stats.input, hex::open_range(&stats.input, &stats.range))?;
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            let result = hex::stats::print_stats(stats, offset, &mut reader, &mut stdout);
+            return check_input(&stats.input, result);
+        }
+        Some(hex::Command::Hash(ref hash)) => {
+            let (mut reader, offset) =
+                check_input(&hash.input, hex::open_range(&hash.input, &hash.range))?;
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            let result = hex::hash::print_hashes(hash, offset, &mut reader, &mut stdout);
+            return check_input(&hash.input, result);
+        }
+        Some(hex::Command::Elf(ref elf)) => {
+            let mut file = fs::File::open(&elf.input)?;
+            let parsed = hex::formats::elf::ElfFile::parse(&mut file).and_then(|parsed| {
+                match elf.section {
+                    Some(ref name) => hex::formats::elf::section_range(&parsed, name).map(Some),
+                    None => Ok(None),
+                }
+                .map(|range| (parsed, range))
+            });
+            let stdout = io::stdout();
+            let mut out = io::BufWriter::with_capacity(hex::OUTPUT_BUFFER, stdout.lock());
+            match parsed {
+                Ok((_, Some((offset, len)))) => {
+                    hex::formats::dump_range(&mut file, offset, len, &mut out)?
+                }
+                Ok((parsed, None)) => hex::formats::elf::print_elf(&parsed, &mut out)?,
+                Err(e) => fail(&elf.input, e),
+            }
+            return out.flush();
+        }
+        Some(hex::Command::Pe(ref pe)) => {
+            let mut file = fs::File::open(&pe.input)?;
+            let parsed = hex::formats::pe::PeFile::parse(&mut file).and_then(|parsed| {
+                hex::formats::pe::selected_range(&parsed, pe).map(|range| (parsed, range))
+            });
+            let stdout = io::stdout();
+            let mut out = io::BufWriter::with_capacity(hex::OUTPUT_BUFFER, stdout.lock());
+            match parsed {
+                Ok((_, Some((offset, len)))) => {
+                    hex::formats::dump_range(&mut file, offset, len, &mut out)?
+                }
+                Ok((parsed, None)) => hex::formats::pe::print_pe(&parsed, &mut out)?,
+                Err(e) => fail(&pe.input, e),
+            }
+            return out.flush();
+        }
+        Some(hex::Command::Image(ref image)) => {
+            let data = fs::read(&image.input)?;
+            let parsed = match hex::formats::image::Image::parse(&data) {
+                Ok(parsed) => parsed,
+                Err(e) => fail(&image.input, e),
+            };
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            return hex::formats::image::print_image(&parsed, &data, image, &mut stdout);
+        }
+        Some(hex::Command::Zip(ref zip)) => {
+            let mut file = fs::File::open(&zip.input)?;
+            let parsed = hex::formats::zip::ZipArchive::parse(&mut file).and_then(|parsed| {
+                match zip.entry {
+                    Some(ref name) => hex::formats::zip::entry_range(&parsed, name).map(Some),
+                    None => Ok(None),
+                }
+                .map(|range| (parsed, range))
+            });
+            let stdout = io::stdout();
+            let mut out = io::BufWriter::with_capacity(hex::OUTPUT_BUFFER, stdout.lock());
+            match parsed {
+                Ok((_, Some((offset, len)))) => {
+                    hex::formats::dump_range(&mut file, offset, len, &mut out)?
+                }
+                Ok((parsed, None)) => hex::formats::zip::print_zip(&parsed, &mut out)?,
+                Err(e) => fail(&zip.input, e),
+            }
+            return out.flush();
+        }
+        Some(hex::Command::Identify(ref identify)) => {
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            return hex::magic::print_identify(identify, &mut stdout);
+        }
+        None => {}
     }
 
-    let file = fs::File::open(&opt.input)?;
-    let mut reader = io::BufReader::new(file);
+    if let Some(skip) = opt.od_skip {
+        opt.range.skip = Some(skip);
+    }
+    if let Some(length) = opt.od_length {
+        opt.range.length = Some(length);
+    }
 
-    let offset = if let Some(skip) = opt.skip {
-        reader.seek(SeekFrom::Start(skip.try_into().unwrap()))?;
-        skip
+    let builtin = opt.one_byte_octal
+        || opt.one_byte_char
+        || opt.one_byte_named
+        || opt.two_bytes_octal
+        || opt.two_bytes_hex
+        || opt.canonical
+        || opt.decimal;
+    let format_writer = format_writer(&opt)?;
+    let od_layout = opt.compat == Some(Compat::Od)
+        || !opt.od_types.is_empty()
+        || opt.address_radix.is_some()
+        || opt.width.is_some();
+    if od_layout && opt.compat.is_some_and(|compat| compat != Compat::Od) {
+        clap::Error::with_description(
+            "-t, -A and -w can't be combined with --compat hexdump or --compat xxd",
+            clap::ErrorKind::ArgumentConflict,
+        )
+        .exit()
+    }
+    let od_types: Vec<OdType> = if opt.od_types.is_empty() {
+        vec![OdType::DEFAULT]
     } else {
-        0
+        opt.od_types
+            .iter()
+            .flat_map(|list| list.0.clone())
+            .collect()
     };
+    if opt.sector == Some(0) {
+        clap::Error::with_description("sectors must not be empty", clap::ErrorKind::InvalidValue)
+            .exit()
+    }
+    let od_address = opt.address_radix.unwrap_or(AddressRadix::Octal);
+    let od_width = opt.width.unwrap_or(16);
+    if od_layout {
+        match hex::od::row_width(&od_types, od_width) {
+            Ok(width) if width != od_width => eprintln!(
+                "warning: invalid width {}; using {} instead",
+                od_width, width
+            ),
+            Ok(_) => {}
+            Err(e) => {
+                clap::Error::with_description(&e.to_string(), clap::ErrorKind::InvalidValue).exit()
+            }
+        }
+    }
+    if !builtin && format_writer.is_none() && opt.compat.is_none() && !od_layout {
+        opt.canonical = true;
+    }
+    // `hexdump` and `od` replace repeated rows with `*`.
+    let squeeze = (od_layout || opt.compat == Some(Compat::Hexdump)) && !opt.no_squeeze;
 
-    let writers = {
+    // Built-in displays take the offset column's width, which depends on the input.
+    let display_writers = |offset_width: usize| {
         let mut writers: Vec<Box<dyn LineWriter>> = vec![];
         if opt.one_byte_octal {
             writers.push(Box::new(hex::OneByteOctal));
         }
         if opt.one_byte_char {
-            writers.push(Box::new(hex::OneByteChar));
+            writers.push(Box::new(hex::OneByteChar::new(opt.charset)));
+        }
+        if opt.one_byte_named {
+            writers.push(Box::new(hex::OneByteNamed));
         }
         if opt.canonical {
-            writers.push(Box::new(hex::CanonicalWriter));
+            writers.push(Box::new(hex::CanonicalWriter::new(
+                opt.charset,
+                opt.nonprintable,
+            )));
         }
         if opt.decimal {
             writers.push(Box::new(hex::DecimalWriter));
@@ -52,14 +257,225 @@ fn main() -> io::Result<()> {
         if opt.two_bytes_hex {
             writers.push(Box::new(hex::TwoBytesHex));
         }
+        if let Some(sector) = opt.sector {
+            writers = writers
+                .into_iter()
+                .enumerate()
+                .map(|(i, w)| {
+                    Box::new(hex::SectorColumn::new(w, sector, i == 0)) as Box<dyn LineWriter>
+                })
+                .collect();
+        } else if opt.offset_format.is_some() || offset_width != 8 {
+            let format = opt.offset_format.unwrap_or(OffsetFormat::Hex);
+            writers = writers
+                .into_iter()
+                .map(|w| {
+                    Box::new(hex::OffsetColumn::new(w, format, offset_width)) as Box<dyn LineWriter>
+                })
+                .collect();
+        }
+        if let Some(ref writer) = format_writer {
+            writers.push(Box::new(writer.clone()));
+        }
+        let mut group = match opt.compat {
+            Some(compat @ Compat::Hexdump) | Some(compat @ Compat::Xxd) => vec![compat.writer()],
+            _ if od_layout => hex::od::writers(&od_types, od_address, od_width).unwrap(),
+            _ => vec![],
+        };
+        if squeeze {
+            group = vec![Box::new(hex::compat::Squeeze::new(group))];
+        }
+        writers.extend(group);
         writers
     };
+    let row_sizes: Vec<usize> = display_writers(8).iter().map(|w| w.row_size()).collect();
+    if let Some(pair) = row_sizes.windows(2).find(|pair| pair[0] != pair[1]) {
+        clap::Error::with_description(
+            &format!(
+                "rows of {} and of {} bytes can't be shown together",
+                pair[0], pair[1]
+            ),
+            clap::ErrorKind::ArgumentConflict,
+        )
+        .exit()
+    }
+
+    let row_size = row_sizes.first().copied().unwrap_or(16);
+    let mut range = opt.range.clone();
+
+    let input = match opt.input {
+        Some(ref input) => input,
+        None => clap::Error::with_description(
+            "The following required arguments were not provided:\n    <input>",
+            clap::ErrorKind::MissingRequiredArgument,
+        )
+        .exit(),
+    };
+    let member = match opt.member {
+        Some(ref name) => Some((input.clone(), name.clone())),
+        None => hex::archive::split_member_spec(input),
+    };
+    if opt.list_members {
+        let members = match hex::archive::list_members(input, range.decompress) {
+            Ok(members) => members,
+            Err(e) => fail(input, e),
+        };
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        return hex::archive::print_members(&members, &mut stdout);
+    }
+
+    // The tail of input with a size is found by seeking; other input is read to its end.
+    let range_start = range.skip.unwrap_or(0);
+    // With `--align`, the first row starts `lead` bytes ahead of the input.
+    let shown_start =
+        if opt.relative { 0 } else { range_start } + u128::from(opt.display_offset.unwrap_or(0));
+    let lead = if opt.align {
+        (shown_start % row_size as u128) as usize
+    } else {
+        0
+    };
+    // Regular files and block devices have a size, found without reading them.
+    let size = match member {
+        None if !range.decompress => hex::device::size(input).ok().flatten(),
+        _ => None,
+    };
+    let mut tail = opt.tail;
+    if let Some(end) = tail {
+        if let Some(size) = size {
+            let len = u128::from(size).saturating_sub(range_start);
+            let len = range.length.map_or(len, |length| len.min(length));
+            let dropped = end.start(len, row_size, lead);
+            range.skip = Some(range_start + dropped);
+            range.length = Some(len - dropped);
+            tail = None;
+        }
+    }
+
+    let mut container_base = None;
+    let (source, (mut reader, mut offset)) = match member {
+        Some((ref archive, ref name)) => {
+            let (reader, member) = match hex::archive::open_member(archive, name, range.decompress)
+            {
+                Ok(opened) => opened,
+                Err(e) => fail(archive, e),
+            };
+            if opt.container_offsets {
+                match member.data_offset {
+                    Some(base) => container_base = Some(base),
+                    None => fail(
+                        archive,
+                        format!("{:?} is compressed, so it has no container offsets", name),
+                    ),
+                }
+            }
+            let (reader, offset) = check_input(archive, hex::stream_range(reader, &range))?;
+            (archive.as_path(), (Input::Stream(reader), offset))
+        }
+        None if opt.container_offsets => fail(input, "--container-offsets needs an archive member"),
+        None => (
+            input.as_path(),
+            check_input(input, Input::open(input, &range, !opt.no_mmap))?,
+        ),
+    };
 
-    if let Some(len) = opt.length {
-        hex::print_lines(&writers, offset, &mut reader.take(len.try_into().unwrap()))?;
+    if let Some(tail) = tail {
+        offset += check_input(source, reader.tail(tail, row_size, lead))?;
+    }
+
+    if let Some(ref path) = opt.template {
+        let source = fs::read_to_string(path)?;
+        let len = reader.as_slice().map(|data| data.len() as u128);
+        let fields = hex::template::parse(&source)
+            .and_then(|template| hex::template::decode(&template, &mut reader, offset, len));
+        let fields = match fields {
+            Ok(fields) => fields,
+            Err(e) => fail(path, e),
+        };
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        return hex::template::render(&fields, &mut stdout);
+    }
+
+    if !opt.no_banner && opt.compat.is_none() && !od_layout && io::stdout().is_terminal() {
+        let head = reader.peek(hex::magic::PEEK_LEN)?;
+        if let Some(banner) = hex::magic::banner(&hex::magic::identify(&head)) {
+            println!("{}", banner);
+        }
+    }
+
+    // Offsets are shown from the start of the input or of the range, plus a base.
+    let input_start = offset;
+    let offset = if opt.relative {
+        offset - range_start
     } else {
-        hex::print_lines(&writers, offset, &mut reader)?;
+        offset
+    } + u128::from(opt.display_offset.unwrap_or(0));
+    let len = match reader.as_slice() {
+        Some(data) => Some(data.len() as u128),
+        None => size.map(|size| {
+            let len = u128::from(size).saturating_sub(input_start);
+            range.length.map_or(len, |length| len.min(length))
+        }),
+    };
+    let offset_width = opt
+        .offset_format
+        .unwrap_or(OffsetFormat::Hex)
+        .width(offset + len.unwrap_or(0).saturating_sub(1));
+
+    let make_writers = || {
+        let mut writers = display_writers(offset_width);
+        if opt.entropy {
+            let window = opt.entropy_window.try_into().unwrap_or(usize::MAX);
+            writers = writers
+                .into_iter()
+                .map(|w| Box::new(hex::EntropyGutter::new(w, window)) as Box<dyn LineWriter>)
+                .collect();
+        }
+        if let Some(base) = container_base {
+            writers = writers
+                .into_iter()
+                .map(|w| Box::new(hex::ContainerOffset::new(w, base.into())) as Box<dyn LineWriter>)
+                .collect();
+        }
+        writers
+    };
+
+    if opt.follow {
+        if member.is_some() {
+            fail(input, "--follow can't read archive members");
+        }
+        let skip = input_start.try_into().unwrap_or(u64::MAX);
+        let mut follow = hex::follow::Follow::open(input, skip, offset)?;
+        let writers = make_writers();
+        let stdout = io::stdout();
+        let mut out = io::BufWriter::with_capacity(hex::OUTPUT_BUFFER, stdout.lock());
+        loop {
+            let restart = follow.poll(&writers, &mut out)?;
+            out.flush()?;
+            match restart {
+                Some(restart) => eprintln!("{}: {}", input.display(), restart),
+                None => std::thread::sleep(hex::follow::POLL_INTERVAL),
+            }
+        }
+    }
+
+    // The entropy gutter and squeezing carry state from row to row, so they can't be
+    // split up.
+    let threads = match opt.threads {
+        _ if opt.entropy || squeeze => 1,
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::with_capacity(hex::OUTPUT_BUFFER, stdout.lock());
+    let mut offset = offset;
+    if opt.align {
+        let head = hex::print_aligned_head(&make_writers(), offset, &mut reader, &mut out);
+        offset += check_input(source, head)? as u128;
     }
+    let printed = reader.print_parallel(&make_writers, offset, threads, &mut out);
+    check_input(source, printed.and_then(|()| out.flush()))?;
 
     Ok(())
 }
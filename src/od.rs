@@ -0,0 +1,456 @@
+//! Rows in the layout of POSIX `od`, with one line per `-t` type.
+
+use crate::{render, ChunkData, LineWriter};
+use byteorder::{ByteOrder, NativeEndian};
+use std::io::{self, Write};
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum OdError {
+    #[error("invalid type string {0:?}")]
+    BadType(String),
+    #[error("invalid type string {spec:?}: {size} byte integers are not supported")]
+    BadSize { spec: String, size: usize },
+    #[error("invalid type string {0:?}: floating-point types are not supported")]
+    Float(String),
+    #[error("invalid address radix {0:?}; expected d, o, x or n")]
+    BadRadix(String),
+    #[error("invalid width 0; rows must hold at least one byte")]
+    ZeroWidth,
+}
+
+/// How offsets are shown, given with `-A`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AddressRadix {
+    Decimal,
+    Octal,
+    Hex,
+    None,
+}
+
+impl FromStr for AddressRadix {
+    type Err = OdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "d" => Ok(AddressRadix::Decimal),
+            "o" => Ok(AddressRadix::Octal),
+            "x" => Ok(AddressRadix::Hex),
+            "n" => Ok(AddressRadix::None),
+            _ => Err(OdError::BadRadix(s.to_string())),
+        }
+    }
+}
+
+impl AddressRadix {
+    /// The minimum number of digits shown.
+    fn width(self) -> usize {
+        match self {
+            AddressRadix::Decimal | AddressRadix::Octal => 7,
+            AddressRadix::Hex => 6,
+            AddressRadix::None => 0,
+        }
+    }
+
+    fn write(self, offset: u128, w: &mut dyn Write) -> io::Result<()> {
+        match self {
+            AddressRadix::Decimal => write!(w, "{:07}", offset),
+            AddressRadix::Octal => write!(w, "{:07o}", offset),
+            AddressRadix::Hex => write!(w, "{:06x}", offset),
+            AddressRadix::None => Ok(()),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Format {
+    /// `a`: named characters.
+    Named,
+    /// `c`: characters, C escapes or octal.
+    Char,
+    /// `d`: signed decimal.
+    Signed,
+    /// `o`: octal.
+    Octal,
+    /// `u`: unsigned decimal.
+    Unsigned,
+    /// `x`: hexadecimal.
+    Hex,
+}
+
+/// One type of a `-t` type string.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OdType {
+    pub format: Format,
+    /// Bytes per field.
+    pub size: usize,
+    /// Whether the row ends with its printable characters between `>` and `<`.
+    pub text: bool,
+}
+
+impl OdType {
+    /// `od` without `-t`: two-byte octal words.
+    pub const DEFAULT: OdType = OdType {
+        format: Format::Octal,
+        size: 2,
+        text: false,
+    };
+
+    /// Characters needed for the widest value of a field.
+    fn field_width(&self) -> usize {
+        let size = self.size;
+        match self.format {
+            Format::Named | Format::Char => 3,
+            Format::Octal => (size * 8).div_ceil(3),
+            Format::Hex => size * 2,
+            Format::Unsigned => [3, 5, 10, 20][size.trailing_zeros() as usize],
+            Format::Signed => [4, 6, 11, 20][size.trailing_zeros() as usize],
+        }
+    }
+}
+
+/// The types of one `-t` argument, such as `x1z` or `d2c`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeList(pub Vec<OdType>);
+
+impl FromStr for TypeList {
+    type Err = OdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = s.as_bytes();
+        let mut types = vec![];
+        let mut at = 0;
+        while at < bytes.len() {
+            let format = match bytes[at] {
+                b'a' => Format::Named,
+                b'c' => Format::Char,
+                b'd' => Format::Signed,
+                b'o' => Format::Octal,
+                b'u' => Format::Unsigned,
+                b'x' => Format::Hex,
+                b'f' => return Err(OdError::Float(s.to_string())),
+                _ => return Err(OdError::BadType(s.to_string())),
+            };
+            at += 1;
+            let mut size = 1;
+            if !matches!(format, Format::Named | Format::Char) {
+                let rest = &s[at..];
+                let (parsed, used) = match rest.as_bytes().first() {
+                    Some(b'C') => (1, 1),
+                    Some(b'S') => (2, 1),
+                    Some(b'I') => (4, 1),
+                    Some(b'L') => (8, 1),
+                    _ => match rest.bytes().take_while(u8::is_ascii_digit).count() {
+                        0 => (4, 0),
+                        digits => (rest[..digits].parse().unwrap_or(usize::MAX), digits),
+                    },
+                };
+                if ![1, 2, 4, 8].contains(&parsed) {
+                    return Err(OdError::BadSize {
+                        spec: s.to_string(),
+                        size: parsed,
+                    });
+                }
+                size = parsed;
+                at += used;
+            }
+            let text = bytes.get(at) == Some(&b'z');
+            if text {
+                at += 1;
+            }
+            types.push(OdType { format, size, text });
+        }
+        if types.is_empty() {
+            return Err(OdError::BadType(s.to_string()));
+        }
+        Ok(TypeList(types))
+    }
+}
+
+/// Names of ASCII characters for `od -a`, which ignores the top bit.
+pub(crate) const NAMED_CHARS: [&str; 33] = [
+    "nul", "soh", "stx", "etx", "eot", "enq", "ack", "bel", "bs", "ht", "nl", "vt", "ff", "cr",
+    "so", "si", "dle", "dc1", "dc2", "dc3", "dc4", "nak", "syn", "etb", "can", "em", "sub", "esc",
+    "fs", "gs", "rs", "us", "sp",
+];
+
+/// `byte` as `od -a` shows it.
+pub(crate) fn named_char(byte: u8, buf: &mut [u8; 3]) -> &[u8] {
+    let byte = byte & 0x7f;
+    match byte {
+        0..=0x20 => NAMED_CHARS[byte as usize].as_bytes(),
+        0x7f => b"del",
+        _ => {
+            buf[0] = byte;
+            &buf[..1]
+        }
+    }
+}
+
+/// `byte` as `od -c` shows it: itself if printable, a C escape, or three octal digits.
+pub(crate) fn escaped_char(byte: u8, buf: &mut [u8; 3]) -> &[u8] {
+    let escape = match byte {
+        0 => b'0',
+        0x07 => b'a',
+        0x08 => b'b',
+        0x0c => b'f',
+        b'\n' => b'n',
+        b'\r' => b'r',
+        b'\t' => b't',
+        0x0b => b'v',
+        0x20..=0x7e => {
+            buf[0] = byte;
+            return &buf[..1];
+        }
+        _ => {
+            *buf = render::OCTAL[byte as usize];
+            return &buf[..];
+        }
+    };
+    buf[0] = b'\\';
+    buf[1] = escape;
+    &buf[..2]
+}
+
+/// One line of each row: the fields of one type.
+pub struct OdWriter {
+    ty: OdType,
+    address: AddressRadix,
+    /// Whether this is the first line of the row, which shows the offset.
+    first: bool,
+    /// Bytes per row.
+    width: usize,
+    /// Spaces shared out between the fields, so that the lines of all types align.
+    pad: usize,
+}
+
+/// The bytes per row to show for `width`. As in `od`, a width that isn't a multiple of
+/// the sizes of all the types becomes the largest size instead.
+pub fn row_width(types: &[OdType], width: usize) -> Result<usize, OdError> {
+    if width == 0 {
+        return Err(OdError::ZeroWidth);
+    }
+    let multiple = types.iter().map(|ty| ty.size).max().unwrap_or(1);
+    Ok(if width.is_multiple_of(multiple) {
+        width
+    } else {
+        multiple
+    })
+}
+
+/// The writers for `types`, one line per type and row of `width` bytes, adjusted by
+/// `row_width`.
+pub fn writers(
+    types: &[OdType],
+    address: AddressRadix,
+    width: usize,
+) -> Result<Vec<Box<dyn LineWriter>>, OdError> {
+    let width = row_width(types, width)?;
+    let line_width = types
+        .iter()
+        .map(|ty| (ty.field_width() + 1) * (width / ty.size))
+        .max()
+        .unwrap_or(0);
+    Ok(types
+        .iter()
+        .enumerate()
+        .map(|(i, &ty)| {
+            Box::new(OdWriter {
+                ty,
+                address,
+                first: i == 0,
+                width,
+                pad: line_width - ty.field_width() * (width / ty.size),
+            }) as Box<dyn LineWriter>
+        })
+        .collect())
+}
+
+impl OdWriter {
+    fn write_field(&self, field: &[u8], width: usize, line: &mut Vec<u8>) -> io::Result<()> {
+        let digits = self.ty.field_width();
+        let mut buf = [0u8; 3];
+        match self.ty.format {
+            Format::Named => write_padded(named_char(field[0], &mut buf), width, line),
+            Format::Char => write_padded(escaped_char(field[0], &mut buf), width, line),
+            Format::Signed => {
+                let value = NativeEndian::read_int(field, field.len());
+                write!(line, "{:>width$}", value, width = width)
+            }
+            Format::Unsigned => {
+                let value = NativeEndian::read_uint(field, field.len());
+                write!(line, "{:>width$}", value, width = width)
+            }
+            Format::Octal => {
+                let value = NativeEndian::read_uint(field, field.len());
+                let value = format!("{:0digits$o}", value, digits = digits);
+                write!(line, "{:>width$}", value, width = width)
+            }
+            Format::Hex => {
+                let value = NativeEndian::read_uint(field, field.len());
+                let value = format!("{:0digits$x}", value, digits = digits);
+                write!(line, "{:>width$}", value, width = width)
+            }
+        }
+    }
+}
+
+fn write_padded(text: &[u8], width: usize, line: &mut Vec<u8>) -> io::Result<()> {
+    line.resize(line.len() + width.saturating_sub(text.len()), b' ');
+    line.write_all(text)
+}
+
+impl LineWriter for OdWriter {
+    fn print_idx(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()> {
+        if self.first {
+            self.address.write(data.offset, w)
+        } else {
+            write!(w, "{:1$}", "", self.address.width())
+        }
+    }
+
+    fn print_chunk(&self, data: &ChunkData<'_>, w: &mut dyn Write) -> io::Result<()> {
+        let size = self.ty.size;
+        let fields = self.width / size;
+        let row = data.padded();
+        let shown = row.len().div_ceil(size);
+        let mut line = Vec::with_capacity(self.width * 5);
+
+        // Like `od`, give each field its share of the padding, the earlier fields
+        // getting the larger shares.
+        let mut pad_remaining = self.pad;
+        for i in (fields - shown + 1..=fields).rev() {
+            let next_pad = self.pad * (i - 1) / fields;
+            let width = pad_remaining - next_pad + self.ty.field_width();
+            pad_remaining = next_pad;
+
+            // A field in the blank cells, even partly, is left blank, and one cut short
+            // by the end of the input is zero-filled.
+            let start = (fields - i) * size;
+            if start < data.pad {
+                line.resize(line.len() + width, b' ');
+                continue;
+            }
+            let available = &row[start..row.len().min(start + size)];
+            let mut field = [0u8; 8];
+            field[..available.len()].copy_from_slice(available);
+            self.write_field(&field[..size], width, &mut line)?;
+        }
+
+        if self.ty.text {
+            let blank = (self.width - row.len()) / size;
+            line.resize(line.len() + blank * (self.ty.field_width() + 1), b' ');
+            line.extend_from_slice(b"  >");
+            line.resize(line.len() + data.pad, b' ');
+            line.extend(data.chunk.iter().map(|&b| render::PRINTABLE[b as usize]));
+            line.push(b'<');
+        }
+        line.push(b'\n');
+        w.write_all(&line)
+    }
+
+    fn row_size(&self) -> usize {
+        self.width
+    }
+
+    fn print_end(&self, _start: u128, end: u128, w: &mut dyn Write) -> io::Result<()> {
+        if !self.first || self.address == AddressRadix::None {
+            return Ok(());
+        }
+        self.address.write(end, w)?;
+        writeln!(w)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compat::Squeeze;
+    use crate::print_slice_to;
+
+    const SAMPLE: &[u8] = include_bytes!("../tests/fixtures/compat/sample.bin");
+
+    fn render(types: &str, address: &str, width: usize) -> String {
+        let types: Vec<OdType> = types
+            .split_whitespace()
+            .flat_map(|spec| spec.parse::<TypeList>().unwrap().0)
+            .collect();
+        let inner = writers(&types, address.parse().unwrap(), width).unwrap();
+        let writers: Vec<Box<dyn LineWriter>> = vec![Box::new(Squeeze::new(inner))];
+        let mut out = vec![];
+        print_slice_to(&writers, 0, SAMPLE, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn matches_golden_files() {
+        assert_eq!(
+            render("x1 c d2", "o", 16),
+            include_str!("../tests/fixtures/compat/sample.od-x1-c-d2")
+        );
+        assert_eq!(
+            render("a u4z", "x", 8),
+            include_str!("../tests/fixtures/compat/sample.od-a-u4z-Ax-w8")
+        );
+        assert_eq!(
+            render("o1 x8 dC", "n", 16),
+            include_str!("../tests/fixtures/compat/sample.od-o1-x8-dC-An")
+        );
+    }
+
+    #[test]
+    fn aligned_row_after_odd_skip() {
+        let types: Vec<OdType> = "o2x1z".parse::<TypeList>().unwrap().0;
+        let writers = writers(&types, AddressRadix::Octal, 16).unwrap();
+        let mut out = vec![];
+        let mut reader = &b"\x46\x47\x48\x49\x4a"[..];
+        crate::print_aligned_head(&writers, 0o13, &mut reader, &mut out).unwrap();
+        // The halfword at 012 is only half read, so it is left blank.
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "0000000                                           044107 045111\n\
+             \x20                                              46  47 48  49 4a  >           FGHIJ<\n"
+        );
+    }
+
+    #[test]
+    fn type_strings() {
+        let parsed: TypeList = "x2zcdL".parse().unwrap();
+        assert_eq!(
+            parsed.0,
+            vec![
+                OdType {
+                    format: Format::Hex,
+                    size: 2,
+                    text: true
+                },
+                OdType {
+                    format: Format::Char,
+                    size: 1,
+                    text: false
+                },
+                OdType {
+                    format: Format::Signed,
+                    size: 8,
+                    text: false
+                },
+            ]
+        );
+        assert_eq!("o".parse::<TypeList>().unwrap().0[0].size, 4);
+        assert!("x3".parse::<TypeList>().is_err());
+        assert!("f4".parse::<TypeList>().is_err());
+        assert_eq!(
+            writers(&[OdType::DEFAULT], AddressRadix::Octal, 0)
+                .err()
+                .unwrap()
+                .to_string(),
+            "invalid width 0; rows must hold at least one byte"
+        );
+        let types = "x2x4".parse::<TypeList>().unwrap().0;
+        assert_eq!(row_width(&types, 6).unwrap(), 4);
+        assert_eq!(row_width(&types, 8).unwrap(), 8);
+        let writers = writers(&types, AddressRadix::Octal, 6).unwrap();
+        assert_eq!(crate::row_size(&writers), 4);
+    }
+}
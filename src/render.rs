@@ -0,0 +1,90 @@
+//! Lookup tables and digit formatting shared by the line writers.
+
+use std::io::{self, Write};
+
+/// Upper-case hex digit pairs for every byte value.
+pub(crate) static HEX_UPPER: [[u8; 2]; 256] = hex_pairs(b"0123456789ABCDEF");
+/// Lower-case hex digit pairs for every byte value.
+pub(crate) static HEX_LOWER: [[u8; 2]; 256] = hex_pairs(b"0123456789abcdef");
+/// Three-digit octal for every byte value.
+pub(crate) static OCTAL: [[u8; 3]; 256] = octal_triples();
+/// Printable ASCII as itself, everything else as `.`.
+pub(crate) static PRINTABLE: [u8; 256] = printable();
+
+const fn hex_pairs(digits: &[u8; 16]) -> [[u8; 2]; 256] {
+    let mut table = [[0u8; 2]; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[i] = [digits[i >> 4], digits[i & 0xf]];
+        i += 1;
+    }
+    table
+}
+
+const fn octal_triples() -> [[u8; 3]; 256] {
+    let mut table = [[0u8; 3]; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[i] = [
+            b'0' + (i >> 6) as u8,
+            b'0' + ((i >> 3) & 7) as u8,
+            b'0' + (i & 7) as u8,
+        ];
+        i += 1;
+    }
+    table
+}
+
+const fn printable() -> [u8; 256] {
+    let mut table = [b'.'; 256];
+    let mut i = 0x20;
+    while i < 0x7f {
+        table[i] = i as u8;
+        i += 1;
+    }
+    table
+}
+
+/// Fill `buf` with `n` in `radix`, zero-padded on the left to the width of `buf`.
+pub(crate) fn put_digits(buf: &mut [u8], mut n: u64, radix: u64) {
+    for slot in buf.iter_mut().rev() {
+        *slot = b"0123456789ABCDEF"[(n % radix) as usize];
+        n /= radix;
+    }
+}
+
+/// Write `{:#010x}\t` for `offset` without going through the formatting machinery.
+pub(crate) fn write_offset(w: &mut dyn Write, offset: u128) -> io::Result<()> {
+    if offset > u128::from(u32::MAX) {
+        return write!(w, "{:#010x}\t", offset);
+    }
+    let mut buf = *b"0x00000000\t";
+    for (i, byte) in (offset as u32).to_be_bytes().iter().enumerate() {
+        buf[2 + i * 2..4 + i * 2].copy_from_slice(&HEX_LOWER[*byte as usize]);
+    }
+    w.write_all(&buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tables_match_format() {
+        for byte in 0..=255u8 {
+            let i = byte as usize;
+            assert_eq!(HEX_UPPER[i], format!("{:02X}", byte).as_bytes());
+            assert_eq!(HEX_LOWER[i], format!("{:02x}", byte).as_bytes());
+            assert_eq!(OCTAL[i], format!("{:03o}", byte).as_bytes());
+        }
+        let mut buf = [0u8; 6];
+        put_digits(&mut buf, 0o177553, 8);
+        assert_eq!(&buf, b"177553");
+
+        for &offset in &[0u128, 0x10, 0xdead_beef, 0x1_0000_0000] {
+            let mut out = vec![];
+            write_offset(&mut out, offset).unwrap();
+            assert_eq!(out, format!("{:#010x}\t", offset).as_bytes());
+        }
+    }
+}
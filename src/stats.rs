@@ -0,0 +1,224 @@
+use crate::{byte_unit::parse_block_size, read_to_fill, RangeOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct StatsOptions {
+    /// Size of the window used for per-block entropy.
+    #[structopt(short = "w", long = "block", default_value = "4KiB", parse(try_from_str = parse_block_size))]
+    pub block: usize,
+    /// Print the full byte histogram.
+    #[structopt(long = "histogram")]
+    pub histogram: bool,
+
+    #[structopt(flatten)]
+    pub range: RangeOptions,
+
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    pub input: PathBuf,
+}
+
+/// Number of occurrences of each byte value.
+#[derive(Clone)]
+pub struct Histogram {
+    counts: [u64; 256],
+    total: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            counts: [0; 256],
+            total: 0,
+        }
+    }
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.counts[byte as usize] += 1;
+        }
+        self.total += bytes.len() as u64;
+    }
+
+    pub fn count(&self, byte: u8) -> u64 {
+        self.counts[byte as usize]
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Shannon entropy in bits per byte, between 0 and 8.
+    pub fn entropy(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        let total = self.total as f64;
+        self.counts
+            .iter()
+            .filter(|&&count| count > 0)
+            .map(|&count| {
+                let p = count as f64 / total;
+                p * (1.0 / p).log2()
+            })
+            .sum()
+    }
+}
+
+/// Shannon entropy of `bytes` in bits per byte.
+pub fn entropy(bytes: &[u8]) -> f64 {
+    let mut histogram = Histogram::new();
+    histogram.add(bytes);
+    histogram.entropy()
+}
+
+const SHADES: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const EIGHTHS: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+/// A single sparkline glyph for `fraction` (clamped to 0..=1).
+pub fn spark(fraction: f64) -> char {
+    let fraction = fraction.clamp(0.0, 1.0);
+    SHADES[((fraction * 7.0).round()) as usize]
+}
+
+/// A horizontal bar `width` cells wide, filled to `fraction` (clamped to 0..=1).
+pub fn bar(fraction: f64, width: usize) -> String {
+    let fraction = fraction.clamp(0.0, 1.0);
+    let eighths = (fraction * (width * 8) as f64).round() as usize;
+    let mut out = String::with_capacity(width * 3);
+    for _ in 0..eighths / 8 {
+        out.push('█');
+    }
+    if !eighths.is_multiple_of(8) {
+        out.push(EIGHTHS[eighths % 8]);
+    }
+    out
+}
+
+const BAR_WIDTH: usize = 32;
+/// Bytes read at a time.
+const READ_SIZE: usize = 64 << 10;
+
+/// Print the entropy of each block of `reader`, followed by a summary of the whole input.
+pub fn print_stats(
+    opt: &StatsOptions,
+    offset: u128,
+    reader: &mut dyn io::Read,
+    w: &mut dyn Write,
+) -> io::Result<()> {
+    let block = opt.block;
+    let mut buffer = vec![0u8; block.min(READ_SIZE)];
+    let mut overall = Histogram::new();
+    let mut idx = 0;
+
+    loop {
+        // Count a block a buffer at a time rather than holding it all.
+        let mut histogram = Histogram::new();
+        let mut left = block;
+        while left > 0 {
+            let amt = read_to_fill(reader, &mut buffer[..left.min(READ_SIZE)])?;
+            if amt == 0 {
+                break;
+            }
+            histogram.add(&buffer[..amt]);
+            overall.add(&buffer[..amt]);
+            left -= amt;
+        }
+        if histogram.total() == 0 {
+            break;
+        }
+
+        let value = histogram.entropy();
+        writeln!(
+            w,
+            "{:#010x}\t{:.3}\t{}\t{}",
+            offset + idx * block as u128,
+            value,
+            spark(value / 8.0),
+            bar(value / 8.0, BAR_WIDTH)
+        )?;
+        idx += 1;
+    }
+
+    writeln!(w)?;
+    writeln!(w, "Bytes:\t\t{}", overall.total())?;
+    writeln!(w, "Entropy:\t{:.6} bits/byte", overall.entropy())?;
+    writeln!(
+        w,
+        "Distinct:\t{}",
+        (0..=255u8).filter(|&b| overall.count(b) > 0).count()
+    )?;
+
+    if opt.histogram && overall.total() > 0 {
+        let max = (0..=255u8).map(|b| overall.count(b)).max().unwrap_or(0);
+        writeln!(w)?;
+        for byte in 0..=255u8 {
+            let count = overall.count(byte);
+            if count == 0 {
+                continue;
+            }
+            writeln!(
+                w,
+                "{:#04x}\t{}\t{:.3}%\t{}",
+                byte,
+                count,
+                count as f64 * 100.0 / overall.total() as f64,
+                bar(count as f64 / max as f64, BAR_WIDTH)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entropy_bounds() {
+        assert_eq!(entropy(&[]), 0.0);
+        assert_eq!(entropy(&[0x41; 64]), 0.0);
+        let all: Vec<u8> = (0..=255).collect();
+        assert!((entropy(&all) - 8.0).abs() < 1e-9);
+        assert!((entropy(b"abab") - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn blocks_larger_than_a_read() {
+        let opt = StatsOptions::from_iter(&["stats", "--block", "100000", "in"]);
+        let data: Vec<u8> = (0..150_000u32).map(|i| (i % 251) as u8).collect();
+        let mut out = vec![];
+        print_stats(&opt, 0, &mut &data[..], &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let offsets: Vec<&str> = out
+            .lines()
+            .take_while(|line| !line.is_empty())
+            .map(|line| &line[..10])
+            .collect();
+        assert_eq!(offsets, vec!["0x00000000", "0x000186a0"]);
+        assert!(out.contains("Bytes:\t\t150000\n"));
+
+        let err = StatsOptions::from_iter_safe(&["stats", "--block", "2GiB", "in"]).unwrap_err();
+        assert!(err
+            .message
+            .contains("the block size must be from 1 B to 1 GiB"));
+    }
+
+    #[test]
+    fn bars() {
+        assert_eq!(bar(0.0, 4), "");
+        assert_eq!(bar(0.5, 4), "██");
+        assert_eq!(bar(0.5625, 4), "██▎");
+        assert_eq!(bar(1.0, 4), "████");
+        assert_eq!(spark(0.0), '▁');
+        assert_eq!(spark(1.0), '█');
+    }
+}
@@ -0,0 +1,467 @@
+use crate::{print_lines_to, CanonicalWriter, LineWriter};
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use structopt::StructOpt;
+use thiserror::Error;
+
+#[derive(Debug, StructOpt)]
+pub struct StringsOptions {
+    /// Minimum number of characters in a reported string.
+    #[structopt(short = "m", long = "min-length", default_value = "4")]
+    pub min_length: usize,
+    /// Encodings to search for (ascii, utf8, utf16le, utf16be, utf32le, utf32be or all).
+    #[structopt(
+        short = "e",
+        long = "encoding",
+        default_value = "ascii",
+        use_delimiter = true
+    )]
+    pub encodings: Vec<EncodingSet>,
+    /// Show each string in canonical hex+ASCII form.
+    #[structopt(long = "dump")]
+    pub dump: bool,
+
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    pub input: PathBuf,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Encoding {
+    Ascii,
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Utf32Le,
+    Utf32Be,
+}
+
+impl Encoding {
+    pub const ALL: [Encoding; 6] = [
+        Encoding::Ascii,
+        Encoding::Utf8,
+        Encoding::Utf16Le,
+        Encoding::Utf16Be,
+        Encoding::Utf32Le,
+        Encoding::Utf32Be,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Ascii => "ascii",
+            Encoding::Utf8 => "utf8",
+            Encoding::Utf16Le => "utf16le",
+            Encoding::Utf16Be => "utf16be",
+            Encoding::Utf32Le => "utf32le",
+            Encoding::Utf32Be => "utf32be",
+        }
+    }
+
+    /// Size in bytes of a single code unit.
+    fn unit(self) -> usize {
+        match self {
+            Encoding::Ascii | Encoding::Utf8 => 1,
+            Encoding::Utf16Le | Encoding::Utf16Be => 2,
+            Encoding::Utf32Le | Encoding::Utf32Be => 4,
+        }
+    }
+}
+
+impl Display for Encoding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, Error)]
+#[error("unknown encoding {0:?}")]
+pub struct UnknownEncodingError(String);
+
+/// One `--encoding` argument, which may expand to several encodings.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EncodingSet(pub Vec<Encoding>);
+
+impl FromStr for EncodingSet {
+    type Err = UnknownEncodingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized = s.trim().to_ascii_lowercase().replace('-', "");
+        if normalized == "all" {
+            return Ok(EncodingSet(Encoding::ALL.to_vec()));
+        }
+        Encoding::ALL
+            .iter()
+            .find(|e| e.name() == normalized)
+            .map(|&e| EncodingSet(vec![e]))
+            .ok_or_else(|| UnknownEncodingError(s.to_string()))
+    }
+}
+
+/// A run of printable characters found in the input.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Hit {
+    pub offset: u128,
+    pub encoding: Encoding,
+    pub text: String,
+    pub bytes: Vec<u8>,
+}
+
+fn is_printable(encoding: Encoding, ch: char) -> bool {
+    match encoding {
+        Encoding::Ascii => ch == '\t' || (' '..='~').contains(&ch),
+        _ => ch == '\t' || !(ch.is_control() || ch == char::REPLACEMENT_CHARACTER),
+    }
+}
+
+/// Incremental scanner for one encoding at one code-unit alignment.
+struct Scanner {
+    encoding: Encoding,
+    phase: usize,
+    min_length: usize,
+    unit: Vec<u8>,
+    unit_start: u128,
+    high_surrogate: Option<(u16, u128, Vec<u8>)>,
+    start: u128,
+    text: String,
+    bytes: Vec<u8>,
+    chars: usize,
+}
+
+impl Scanner {
+    fn new(encoding: Encoding, phase: usize, min_length: usize) -> Self {
+        Scanner {
+            encoding,
+            phase,
+            min_length,
+            unit: Vec::with_capacity(4),
+            unit_start: 0,
+            high_surrogate: None,
+            start: 0,
+            text: String::new(),
+            bytes: vec![],
+            chars: 0,
+        }
+    }
+
+    fn feed(&mut self, offset: u128, byte: u8, hits: &mut Vec<Hit>) {
+        match self.encoding {
+            Encoding::Ascii => self.push_char(offset, byte as char, &[byte], hits),
+            Encoding::Utf8 => self.feed_utf8(offset, byte, hits),
+            _ => {
+                let unit = self.encoding.unit();
+                if self.unit.is_empty() {
+                    if (offset % unit as u128) as usize != self.phase {
+                        return;
+                    }
+                    self.unit_start = offset;
+                }
+                self.unit.push(byte);
+                if self.unit.len() == unit {
+                    let raw = std::mem::take(&mut self.unit);
+                    self.feed_unit(raw, hits);
+                }
+            }
+        }
+    }
+
+    fn feed_utf8(&mut self, offset: u128, byte: u8, hits: &mut Vec<Hit>) {
+        if !self.unit.is_empty() {
+            if byte & 0xC0 == 0x80 {
+                self.unit.push(byte);
+                if self.unit.len() == utf8_width::get_width(self.unit[0]) {
+                    let raw = std::mem::take(&mut self.unit);
+                    match std::str::from_utf8(&raw) {
+                        Ok(s) => {
+                            let ch = s.chars().next().unwrap();
+                            self.push_char(self.unit_start, ch, &raw, hits);
+                        }
+                        Err(_) => self.flush(hits),
+                    }
+                }
+                return;
+            }
+            self.unit.clear();
+            self.flush(hits);
+        }
+        match utf8_width::get_width(byte) {
+            1 => self.push_char(offset, byte as char, &[byte], hits),
+            0 => self.flush(hits),
+            _ => {
+                self.unit_start = offset;
+                self.unit.push(byte);
+            }
+        }
+    }
+
+    fn feed_unit(&mut self, raw: Vec<u8>, hits: &mut Vec<Hit>) {
+        let offset = self.unit_start;
+        let value = match self.encoding {
+            Encoding::Utf16Le => u32::from(u16::from_le_bytes([raw[0], raw[1]])),
+            Encoding::Utf16Be => u32::from(u16::from_be_bytes([raw[0], raw[1]])),
+            Encoding::Utf32Le => u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]),
+            Encoding::Utf32Be => u32::from_be_bytes([raw[0], raw[1], raw[2], raw[3]]),
+            Encoding::Ascii | Encoding::Utf8 => unreachable!(),
+        };
+
+        if let Encoding::Utf32Le | Encoding::Utf32Be = self.encoding {
+            match std::char::from_u32(value) {
+                Some(ch) => self.push_char(offset, ch, &raw, hits),
+                None => self.flush(hits),
+            }
+            return;
+        }
+
+        let value = value as u16;
+        if let Some((high, high_offset, mut high_raw)) = self.high_surrogate.take() {
+            if (0xDC00..0xE000).contains(&value) {
+                let ch = std::char::decode_utf16([high, value].iter().copied())
+                    .next()
+                    .and_then(Result::ok);
+                high_raw.extend_from_slice(&raw);
+                match ch {
+                    Some(ch) => self.push_char(high_offset, ch, &high_raw, hits),
+                    None => self.flush(hits),
+                }
+                return;
+            }
+            self.flush(hits);
+        }
+        match value {
+            0xD800..=0xDBFF => self.high_surrogate = Some((value, offset, raw)),
+            0xDC00..=0xDFFF => self.flush(hits),
+            _ => self.push_char(
+                offset,
+                std::char::from_u32(value.into()).unwrap(),
+                &raw,
+                hits,
+            ),
+        }
+    }
+
+    fn push_char(&mut self, offset: u128, ch: char, raw: &[u8], hits: &mut Vec<Hit>) {
+        if !is_printable(self.encoding, ch) {
+            self.flush(hits);
+            return;
+        }
+        if self.chars == 0 {
+            self.start = offset;
+        }
+        self.text.push(ch);
+        self.bytes.extend_from_slice(raw);
+        self.chars += 1;
+    }
+
+    /// Latin-1 text read one byte out of phase as UTF-16 decodes to a run of
+    /// printable characters whose low byte is mostly zero; those are dropped.
+    fn is_misaligned(&self) -> bool {
+        if self.encoding != Encoding::Utf16Le && self.encoding != Encoding::Utf16Be {
+            return false;
+        }
+        let shifted = self
+            .text
+            .chars()
+            .filter(|&ch| (ch as u32) < 0x10000 && ch as u32 & 0xFF == 0)
+            .count();
+        shifted * 2 >= self.chars
+    }
+
+    fn flush(&mut self, hits: &mut Vec<Hit>) {
+        if self.chars >= self.min_length && self.chars > 0 && !self.is_misaligned() {
+            hits.push(Hit {
+                offset: self.start,
+                encoding: self.encoding,
+                text: std::mem::take(&mut self.text),
+                bytes: std::mem::take(&mut self.bytes),
+            });
+        }
+        self.text.clear();
+        self.bytes.clear();
+        self.chars = 0;
+    }
+
+    /// Where the run in progress, if any, starts.
+    fn pending(&self) -> Option<u128> {
+        if self.chars > 0 {
+            Some(self.start)
+        } else if let Some((_, offset, _)) = self.high_surrogate {
+            Some(offset)
+        } else if !self.unit.is_empty() {
+            Some(self.unit_start)
+        } else {
+            None
+        }
+    }
+
+    fn finish(&mut self, hits: &mut Vec<Hit>) {
+        self.unit.clear();
+        self.high_surrogate = None;
+        self.flush(hits);
+    }
+}
+
+/// Scan `reader` for runs of at least `min_length` printable characters in any of
+/// `encodings`, calling `on_hit` for each run found. `offset` is the position of
+/// the first byte read.
+pub fn find_strings(
+    reader: &mut dyn io::Read,
+    offset: u128,
+    min_length: usize,
+    encodings: &[Encoding],
+    on_hit: &mut dyn FnMut(&Hit) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut scanners = vec![];
+    for &encoding in encodings {
+        for phase in 0..encoding.unit() {
+            scanners.push(Scanner::new(encoding, phase, min_length));
+        }
+    }
+
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut hits = vec![];
+    let mut pos = offset;
+    loop {
+        let amt = match reader.read(&mut buffer) {
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if amt == 0 {
+            for scanner in scanners.iter_mut() {
+                scanner.finish(&mut hits);
+            }
+        }
+        for (i, &byte) in buffer[..amt].iter().enumerate() {
+            for scanner in scanners.iter_mut() {
+                scanner.feed(pos + i as u128, byte, &mut hits);
+            }
+        }
+        pos += amt as u128;
+
+        // A run still open may have started before hits already found, so those wait
+        // until it ends to keep the output in offset order.
+        hits.sort_by_key(|hit| hit.offset);
+        let ready = match scanners.iter().filter_map(Scanner::pending).min() {
+            Some(start) => hits.partition_point(|hit| hit.offset < start),
+            None => hits.len(),
+        };
+        for hit in hits.drain(..ready) {
+            on_hit(&hit)?;
+        }
+        if amt == 0 {
+            return Ok(());
+        }
+    }
+}
+
+/// Print every string found in `reader`, one per line, prefixed with its offset.
+pub fn print_strings(
+    opt: &StringsOptions,
+    reader: &mut dyn io::Read,
+    w: &mut dyn Write,
+) -> io::Result<()> {
+    let mut encodings: Vec<Encoding> = vec![];
+    for set in &opt.encodings {
+        for &e in &set.0 {
+            if !encodings.contains(&e) {
+                encodings.push(e);
+            }
+        }
+    }
+    let tagged = encodings.len() > 1;
+    let writers: Vec<Box<dyn LineWriter>> = vec![Box::new(CanonicalWriter::default())];
+
+    find_strings(reader, 0, opt.min_length, &encodings, &mut |hit| {
+        write!(w, "{:#010x}\t", hit.offset)?;
+        if tagged {
+            write!(w, "{}\t", hit.encoding)?;
+        }
+        writeln!(w, "{}", hit.text)?;
+        if opt.dump {
+            print_lines_to(&writers, hit.offset, &mut &hit.bytes[..], w)?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(data: &[u8], encodings: &[Encoding]) -> Vec<(u128, String)> {
+        let mut found = vec![];
+        find_strings(&mut &data[..], 0, 4, encodings, &mut |hit| {
+            found.push((hit.offset, hit.text.clone()));
+            Ok(())
+        })
+        .unwrap();
+        found
+    }
+
+    #[test]
+    fn ascii() {
+        let data = b"\x00\x01hello\xffabc\x00world!\x7f";
+        assert_eq!(
+            collect(data, &[Encoding::Ascii]),
+            vec![(2, "hello".to_string()), (12, "world!".to_string())]
+        );
+    }
+
+    #[test]
+    fn utf8() {
+        let data = "\u{0}caf\u{e9} au lait\u{1}ab".as_bytes();
+        assert_eq!(
+            collect(data, &[Encoding::Utf8]),
+            vec![(1, "caf\u{e9} au lait".to_string())]
+        );
+    }
+
+    #[test]
+    fn utf16() {
+        let mut data = vec![0xFFu8];
+        data.extend("Wide\u{1F600}".encode_utf16().flat_map(|u| u.to_le_bytes()));
+        data.extend_from_slice(&[0, 0]);
+        assert_eq!(
+            collect(&data, &[Encoding::Utf16Le]),
+            vec![(1, "Wide\u{1F600}".to_string())]
+        );
+
+        let mut data = vec![0u8; 3];
+        data.extend("Big!".encode_utf16().flat_map(|u| u.to_be_bytes()));
+        assert_eq!(
+            collect(&data, &[Encoding::Utf16Be]),
+            vec![(3, "Big!".to_string())]
+        );
+    }
+
+    #[test]
+    fn hits_across_blocks_in_offset_order() {
+        // A UTF-16 run straddling the first 64 KiB block starts before an ASCII run
+        // found wholly inside it.
+        let mut data = vec![0u8; 65520];
+        data.extend_from_slice(b"\xe9\0\xe9\0AAAA");
+        data.extend(std::iter::repeat_n(*b"\xe9\0", 6).flatten());
+        data.extend_from_slice(&[0, 0]);
+        assert_eq!(
+            collect(&data, &[Encoding::Ascii, Encoding::Utf16Le]),
+            vec![
+                (
+                    65520,
+                    "\u{e9}\u{e9}\u{4141}\u{4141}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}".to_string()
+                ),
+                (65524, "AAAA".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn utf32() {
+        let mut data = vec![0u8; 4];
+        data.extend("text".chars().flat_map(|c| (c as u32).to_be_bytes()));
+        assert_eq!(
+            collect(&data, &[Encoding::Utf32Be]),
+            vec![(4, "text".to_string())]
+        );
+    }
+}
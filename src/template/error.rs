@@ -0,0 +1,41 @@
+use std::io;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum TemplateError {
+    #[error("line {line}, column {column}: {message}")]
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    #[error("line {line}: {message}")]
+    Decode { line: usize, message: String },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl TemplateError {
+    pub(crate) fn syntax<S: Into<String>>(line: usize, column: usize, message: S) -> Self {
+        TemplateError::Syntax {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn decode<S: Into<String>>(line: usize, message: S) -> Self {
+        TemplateError::Decode {
+            line,
+            message: message.into(),
+        }
+    }
+
+    /// The template line the error refers to, if any.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            TemplateError::Syntax { line, .. } | TemplateError::Decode { line, .. } => Some(*line),
+            TemplateError::Io(_) => None,
+        }
+    }
+}
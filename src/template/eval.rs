@@ -0,0 +1,376 @@
+use super::parse::{BinOp, Endian, Expr, FieldDef, Item, Prim, StructDef, Template, TypeRef, UnOp};
+use super::{Field, TemplateError, Value};
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+use std::io::Read;
+
+/// Structs may refer to themselves through conditionals; this bounds the nesting.
+const MAX_DEPTH: usize = 64;
+
+struct Decoder<'a> {
+    template: &'a Template,
+    reader: &'a mut dyn Read,
+    offset: u128,
+    /// The offset where the input ends, if known.
+    end: Option<u128>,
+    scopes: Vec<HashMap<String, i128>>,
+    fields: Vec<Field>,
+}
+
+impl<'a> Decoder<'a> {
+    fn read(&mut self, len: u128, field: &FieldDef, path: &str) -> Result<Vec<u8>, TemplateError> {
+        let mut raw = vec![];
+        self.reader
+            .take(len.try_into().unwrap_or(u64::MAX))
+            .read_to_end(&mut raw)?;
+        if (raw.len() as u128) < len {
+            return Err(TemplateError::decode(
+                field.line,
+                format!(
+                    "unexpected end of input reading `{}` at offset {:#x} ({} of {} bytes available)",
+                    path,
+                    self.offset,
+                    raw.len(),
+                    len
+                ),
+            ));
+        }
+        self.offset += len;
+        Ok(raw)
+    }
+
+    fn lookup(&self, name: &str) -> Option<i128> {
+        self.scopes.iter().rev().find_map(|s| s.get(name).copied())
+    }
+
+    fn eval(&self, expr: &Expr, line: usize) -> Result<i128, TemplateError> {
+        let overflow = || TemplateError::decode(line, "arithmetic overflow in expression");
+        Ok(match expr {
+            Expr::Int(n) => *n,
+            Expr::Name(name) => self.lookup(name).ok_or_else(|| {
+                TemplateError::decode(line, format!("`{}` is not a decoded integer field", name))
+            })?,
+            Expr::Unary(op, inner) => {
+                let value = self.eval(inner, line)?;
+                match op {
+                    UnOp::Neg => value.checked_neg().ok_or_else(overflow)?,
+                    UnOp::Not => (value == 0) as i128,
+                    UnOp::BitNot => !value,
+                }
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let a = self.eval(lhs, line)?;
+                // Short-circuit, so that `n > 0 && total / n > 4` is safe.
+                match op {
+                    BinOp::And if a == 0 => return Ok(0),
+                    BinOp::Or if a != 0 => return Ok(1),
+                    _ => {}
+                }
+                let b = self.eval(rhs, line)?;
+                match op {
+                    BinOp::Or | BinOp::And => (b != 0) as i128,
+                    BinOp::Eq => (a == b) as i128,
+                    BinOp::Ne => (a != b) as i128,
+                    BinOp::Lt => (a < b) as i128,
+                    BinOp::Le => (a <= b) as i128,
+                    BinOp::Gt => (a > b) as i128,
+                    BinOp::Ge => (a >= b) as i128,
+                    BinOp::BitOr => a | b,
+                    BinOp::BitXor => a ^ b,
+                    BinOp::BitAnd => a & b,
+                    BinOp::Shl => u32::try_from(b)
+                        .ok()
+                        .and_then(|b| a.checked_shl(b))
+                        .ok_or_else(overflow)?,
+                    BinOp::Shr => u32::try_from(b)
+                        .ok()
+                        .and_then(|b| a.checked_shr(b))
+                        .ok_or_else(overflow)?,
+                    BinOp::Add => a.checked_add(b).ok_or_else(overflow)?,
+                    BinOp::Sub => a.checked_sub(b).ok_or_else(overflow)?,
+                    BinOp::Mul => a.checked_mul(b).ok_or_else(overflow)?,
+                    BinOp::Div | BinOp::Rem if b == 0 => {
+                        return Err(TemplateError::decode(
+                            line,
+                            "division by zero in expression",
+                        ))
+                    }
+                    BinOp::Div => a / b,
+                    BinOp::Rem => a % b,
+                }
+            }
+        })
+    }
+
+    fn decode_struct(
+        &mut self,
+        def: &StructDef,
+        path: &str,
+        endian: Endian,
+        line: usize,
+    ) -> Result<(), TemplateError> {
+        if self.scopes.len() >= MAX_DEPTH {
+            return Err(TemplateError::decode(
+                line,
+                format!("structs nested more than {} deep at `{}`", MAX_DEPTH, path),
+            ));
+        }
+        self.scopes.push(HashMap::new());
+        let mut endian = endian;
+        let result = self.items(&def.body, path, &mut endian);
+        self.scopes.pop();
+        result
+    }
+
+    fn items(
+        &mut self,
+        items: &[Item],
+        path: &str,
+        endian: &mut Endian,
+    ) -> Result<(), TemplateError> {
+        for item in items {
+            match item {
+                Item::Endian(e) => *endian = *e,
+                Item::If {
+                    cond,
+                    then,
+                    otherwise,
+                    line,
+                } => {
+                    if self.eval(cond, *line)? != 0 {
+                        self.items(then, path, endian)?;
+                    } else {
+                        self.items(otherwise, path, endian)?;
+                    }
+                }
+                Item::Field(field) => self.field(field, path, *endian)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn field(
+        &mut self,
+        field: &FieldDef,
+        parent: &str,
+        endian: Endian,
+    ) -> Result<(), TemplateError> {
+        let path = if parent.is_empty() {
+            field.name.clone()
+        } else {
+            format!("{}.{}", parent, field.name)
+        };
+        let count = match &field.count {
+            Some(expr) => {
+                let count = self.eval(expr, field.line)?;
+                if count < 0 {
+                    return Err(TemplateError::decode(
+                        field.line,
+                        format!("negative length {} for `{}`", count, path),
+                    ));
+                }
+                Some(count as u128)
+            }
+            None => None,
+        };
+        let start = self.offset;
+
+        let (value, raw) = match (&field.ty, count) {
+            (TypeRef::Char, Some(len)) => {
+                let raw = self.read(len, field, &path)?;
+                (Value::Text(raw.clone()), raw)
+            }
+            (TypeRef::Bytes, Some(len)) => {
+                let raw = self.read(len, field, &path)?;
+                (Value::Bytes(raw.clone()), raw)
+            }
+            (TypeRef::Prim(prim, e), None) => {
+                let raw = self.read(prim.size() as u128, field, &path)?;
+                let value = decode_prim(*prim, e.unwrap_or(endian), &raw);
+                if let Some(n) = value.as_integer() {
+                    self.scopes
+                        .last_mut()
+                        .unwrap()
+                        .insert(field.name.clone(), n);
+                }
+                (value, raw)
+            }
+            (TypeRef::Prim(prim, e), Some(len)) => {
+                let total = len.checked_mul(prim.size() as u128).ok_or_else(|| {
+                    TemplateError::decode(field.line, format!("`{}` is too large", path))
+                })?;
+                let raw = self.read(total, field, &path)?;
+                let values = raw
+                    .chunks(prim.size())
+                    .map(|chunk| decode_prim(*prim, e.unwrap_or(endian), chunk))
+                    .collect();
+                (Value::Array(values), raw)
+            }
+            (TypeRef::LengthPrefixed(prim, e), count) => {
+                let mut values = vec![];
+                let mut raw = vec![];
+                for _ in 0..count.unwrap_or(1) {
+                    let prefix = self.read(prim.size() as u128, field, &path)?;
+                    let len = decode_prim(*prim, e.unwrap_or(endian), &prefix)
+                        .as_integer()
+                        .unwrap_or(0);
+                    let text = self.read(len.max(0) as u128, field, &path)?;
+                    raw.extend_from_slice(&prefix);
+                    raw.extend_from_slice(&text);
+                    values.push(Value::Text(text));
+                }
+                if count.is_none() {
+                    (values.pop().unwrap(), raw)
+                } else {
+                    (Value::Array(values), raw)
+                }
+            }
+            (TypeRef::Struct(name), count) => {
+                let def = self.template.find(name).unwrap();
+                let index = self.fields.len();
+                self.fields.push(Field {
+                    offset: start,
+                    len: 0,
+                    path: path.clone(),
+                    type_name: type_name(field, count),
+                    raw: vec![],
+                    value: Value::Struct,
+                    line: field.line,
+                });
+                match count {
+                    None => self.decode_struct(def, &path, endian, field.line)?,
+                    Some(count) => {
+                        // Every element takes up at least a byte, so no more fit than
+                        // there are bytes left.
+                        if let Some(left) = self.end.map(|end| end.saturating_sub(start)) {
+                            if count > left {
+                                return Err(TemplateError::decode(
+                                    field.line,
+                                    format!(
+                                        "unexpected end of input reading `{}` at offset {:#x} ({} bytes available for {} elements)",
+                                        path, start, left, count
+                                    ),
+                                ));
+                            }
+                        }
+                        for i in 0..count {
+                            let element = format!("{}[{}]", path, i);
+                            let element_index = self.fields.len();
+                            let element_start = self.offset;
+                            self.fields.push(Field {
+                                offset: element_start,
+                                len: 0,
+                                path: element.clone(),
+                                type_name: name.clone(),
+                                raw: vec![],
+                                value: Value::Struct,
+                                line: field.line,
+                            });
+                            self.decode_struct(def, &element, endian, field.line)?;
+                            if self.offset == element_start {
+                                return Err(TemplateError::decode(
+                                    field.line,
+                                    format!("`{}` takes up no bytes", element),
+                                ));
+                            }
+                            self.fields[element_index].len = self.offset - element_start;
+                        }
+                    }
+                }
+                self.fields[index].len = self.offset - start;
+                return Ok(());
+            }
+            (TypeRef::Char, None) | (TypeRef::Bytes, None) => unreachable!(),
+        };
+
+        self.fields.push(Field {
+            offset: start,
+            len: raw.len() as u128,
+            path,
+            type_name: type_name(field, count),
+            raw,
+            value,
+            line: field.line,
+        });
+        Ok(())
+    }
+}
+
+fn type_name(field: &FieldDef, count: Option<u128>) -> String {
+    let base = match &field.ty {
+        TypeRef::Prim(prim, endian) => format!("{}{}", prim_name(*prim), endian_suffix(*endian)),
+        TypeRef::Char => "char".to_string(),
+        TypeRef::Bytes => "bytes".to_string(),
+        TypeRef::LengthPrefixed(prim, endian) => {
+            format!("string<{}{}>", prim_name(*prim), endian_suffix(*endian))
+        }
+        TypeRef::Struct(name) => name.clone(),
+    };
+    match count {
+        Some(count) => format!("{}[{}]", base, count),
+        None => base,
+    }
+}
+
+fn prim_name(prim: Prim) -> &'static str {
+    match prim {
+        Prim::U8 => "u8",
+        Prim::U16 => "u16",
+        Prim::U32 => "u32",
+        Prim::U64 => "u64",
+        Prim::I8 => "i8",
+        Prim::I16 => "i16",
+        Prim::I32 => "i32",
+        Prim::I64 => "i64",
+        Prim::F32 => "f32",
+        Prim::F64 => "f64",
+    }
+}
+
+fn endian_suffix(endian: Option<Endian>) -> &'static str {
+    match endian {
+        None => "",
+        Some(Endian::Little) => "le",
+        Some(Endian::Big) => "be",
+    }
+}
+
+fn decode_prim(prim: Prim, endian: Endian, raw: &[u8]) -> Value {
+    let mut bytes = [0u8; 8];
+    bytes[..raw.len()].copy_from_slice(raw);
+    if endian == Endian::Big {
+        bytes[..raw.len()].reverse();
+    }
+    let bits = u64::from_le_bytes(bytes);
+    match prim {
+        Prim::U8 | Prim::U16 | Prim::U32 | Prim::U64 => Value::Unsigned(bits),
+        Prim::I8 => Value::Signed(bits as u8 as i8 as i64),
+        Prim::I16 => Value::Signed(bits as u16 as i16 as i64),
+        Prim::I32 => Value::Signed(bits as u32 as i32 as i64),
+        Prim::I64 => Value::Signed(bits as i64),
+        Prim::F32 => Value::Float(f32::from_bits(bits as u32) as f64),
+        Prim::F64 => Value::Float(f64::from_bits(bits)),
+    }
+}
+
+/// Decode `reader` according to `template`, returning every field in input order.
+/// `offset` is the position of the first byte read, and `len`, when known, the number
+/// of bytes left to read.
+pub fn decode(
+    template: &Template,
+    reader: &mut dyn Read,
+    offset: u128,
+    len: Option<u128>,
+) -> Result<Vec<Field>, TemplateError> {
+    let root = template.root();
+    let mut decoder = Decoder {
+        template,
+        reader,
+        offset,
+        end: len.map(|len| offset + len),
+        scopes: vec![],
+        fields: vec![],
+    };
+    decoder.decode_struct(root, "", template.endian, root.line)?;
+    Ok(decoder.fields)
+}
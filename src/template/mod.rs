@@ -0,0 +1,288 @@
+//! Declarative binary templates.
+//!
+//! A template describes one or more structs, whose fields are decoded in order
+//! from the input:
+//!
+//! ```text
+//! endian big                  # default byte order, `little` if omitted
+//!
+//! struct header {
+//!     magic: char[4]          # fixed-size text
+//!     version: u16            # u8-u64, i8-i64, f32, f64; `u16le`/`u16be` override the byte order
+//!     count: u32
+//!     if version >= 2 {       # conditionals over earlier integer fields
+//!         flags: u32
+//!     }
+//!     name: string<u8>        # text prefixed with its length
+//!     entries: entry[count]   # arrays of any type, sized by an expression
+//!     padding: bytes[4 - count % 4]
+//! }
+//!
+//! struct entry {
+//!     endian little
+//!     tag: u16
+//!     value: f32
+//! }
+//! ```
+//!
+//! The struct named `main`, or else the first struct, describes the start of the input.
+
+mod error;
+mod eval;
+mod parse;
+
+pub use error::*;
+pub use eval::decode;
+pub use parse::{parse, Template};
+
+use std::io::{self, Write};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Unsigned(u64),
+    Signed(i64),
+    Float(f64),
+    Text(Vec<u8>),
+    Bytes(Vec<u8>),
+    Array(Vec<Value>),
+    Struct,
+}
+
+impl Value {
+    pub fn as_integer(&self) -> Option<i128> {
+        match *self {
+            Value::Unsigned(n) => Some(n.into()),
+            Value::Signed(n) => Some(n.into()),
+            _ => None,
+        }
+    }
+}
+
+/// One decoded field, or the start of a nested struct.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    pub offset: u128,
+    pub len: u128,
+    pub path: String,
+    pub type_name: String,
+    pub raw: Vec<u8>,
+    pub value: Value,
+    /// Template line the field was declared on.
+    pub line: usize,
+}
+
+/// Most raw bytes shown for one field before it is elided.
+const RAW_BYTES: usize = 8;
+/// Most array elements shown for one field before it is elided.
+const ARRAY_ELEMENTS: usize = 16;
+
+fn write_text(bytes: &[u8], w: &mut dyn Write) -> io::Result<()> {
+    let end = bytes.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    write!(w, "\"")?;
+    for &byte in &bytes[..end] {
+        match byte {
+            b'\n' => write!(w, "\\n")?,
+            b'\r' => write!(w, "\\r")?,
+            b'\t' => write!(w, "\\t")?,
+            b'\0' => write!(w, "\\0")?,
+            b'"' => write!(w, "\\\"")?,
+            b'\\' => write!(w, "\\\\")?,
+            0x20..=0x7e => write!(w, "{}", byte as char)?,
+            _ => write!(w, "\\x{:02x}", byte)?,
+        }
+    }
+    write!(w, "\"")
+}
+
+fn write_value(value: &Value, w: &mut dyn Write) -> io::Result<()> {
+    match value {
+        Value::Unsigned(n) => write!(w, "{} ({:#x})", n, n),
+        Value::Signed(n) => write!(w, "{}", n),
+        Value::Float(n) => write!(w, "{}", n),
+        Value::Text(bytes) => write_text(bytes, w),
+        Value::Bytes(bytes) => {
+            for byte in bytes.iter().take(RAW_BYTES * 4) {
+                write!(w, "{:02x}", byte)?;
+            }
+            if bytes.len() > RAW_BYTES * 4 {
+                write!(w, "…")?;
+            }
+            Ok(())
+        }
+        Value::Array(values) => {
+            write!(w, "[")?;
+            for (i, value) in values.iter().take(ARRAY_ELEMENTS).enumerate() {
+                if i > 0 {
+                    write!(w, ", ")?;
+                }
+                match value {
+                    Value::Unsigned(n) => write!(w, "{}", n)?,
+                    other => write_value(other, w)?,
+                }
+            }
+            if values.len() > ARRAY_ELEMENTS {
+                write!(w, ", … ({} total)", values.len())?;
+            }
+            write!(w, "]")
+        }
+        Value::Struct => Ok(()),
+    }
+}
+
+/// Print each field's offset, raw bytes and decoded value, one per line.
+pub fn render(fields: &[Field], w: &mut dyn Write) -> io::Result<()> {
+    for field in fields {
+        write!(w, "{:#010x}\t", field.offset)?;
+        let shown = field.raw.len().min(RAW_BYTES);
+        let mut width = 0;
+        for (i, byte) in field.raw[..shown].iter().enumerate() {
+            if i > 0 {
+                write!(w, " ")?;
+                width += 1;
+            }
+            write!(w, "{:02X}", byte)?;
+            width += 2;
+        }
+        if field.raw.len() > RAW_BYTES {
+            write!(w, "…")?;
+            width += 1;
+        }
+        for _ in width..RAW_BYTES * 3 {
+            write!(w, " ")?;
+        }
+
+        write!(w, "\t{}: {}", field.path, field.type_name)?;
+        if field.value == Value::Struct {
+            writeln!(w, " ({} bytes)", field.len)?;
+        } else {
+            write!(w, " = ")?;
+            write_value(&field.value, w)?;
+            writeln!(w)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEMPLATE: &str = "
+endian big
+
+struct header {
+    magic: char[4]
+    version: u16
+    count: u8
+    if version >= 2 {
+        flags: u16le
+    } else {
+        reserved: bytes[2]
+    }
+    name: string<u8>
+    entries: entry[count]
+}
+
+struct entry {
+    endian little
+    tag: i16
+    value: f32
+}
+";
+
+    #[test]
+    fn decode_fields() {
+        let mut data = vec![];
+        data.extend_from_slice(b"HDR\0");
+        data.extend_from_slice(&[0x00, 0x02, 0x02]);
+        data.extend_from_slice(&[0x01, 0x80]);
+        data.extend_from_slice(&[3, b'a', b'b', b'c']);
+        data.extend_from_slice(&(-2i16).to_le_bytes());
+        data.extend_from_slice(&1.5f32.to_le_bytes());
+        data.extend_from_slice(&7i16.to_le_bytes());
+        data.extend_from_slice(&0.25f32.to_le_bytes());
+
+        let template = parse(TEMPLATE).unwrap();
+        let fields = decode(&template, &mut &data[..], 0x100, Some(data.len() as u128)).unwrap();
+        let summary: Vec<(u128, &str, &Value)> = fields
+            .iter()
+            .map(|f| (f.offset, f.path.as_str(), &f.value))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (0x100, "magic", &Value::Text(b"HDR\0".to_vec())),
+                (0x104, "version", &Value::Unsigned(2)),
+                (0x106, "count", &Value::Unsigned(2)),
+                (0x107, "flags", &Value::Unsigned(0x8001)),
+                (0x109, "name", &Value::Text(b"abc".to_vec())),
+                (0x10d, "entries", &Value::Struct),
+                (0x10d, "entries[0]", &Value::Struct),
+                (0x10d, "entries[0].tag", &Value::Signed(-2)),
+                (0x10f, "entries[0].value", &Value::Float(1.5)),
+                (0x113, "entries[1]", &Value::Struct),
+                (0x113, "entries[1].tag", &Value::Signed(7)),
+                (0x115, "entries[1].value", &Value::Float(0.25)),
+            ]
+        );
+        assert_eq!(fields[5].len, 12);
+
+        let mut out = vec![];
+        render(&fields[..2], &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "0x00000100\t48 44 52 00             \tmagic: char[4] = \"HDR\"\n\
+             0x00000104\t00 02                   \tversion: u16 = 2 (0x2)\n"
+        );
+    }
+
+    #[test]
+    fn syntax_errors_point_at_line() {
+        let err = parse("struct a {\n  x: u8\n  y: u17\n}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 3, column 3: unknown type `u17` for field `y`"
+        );
+
+        let err = parse("struct a {\n  x: u8\n  y u8\n}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 3, column 5: expected `:`, found `u8`"
+        );
+
+        let err = parse("struct a {\n  x: char\n}").unwrap_err();
+        assert_eq!(err.line(), Some(2));
+    }
+
+    #[test]
+    fn decode_errors_point_at_line() {
+        let template = parse("struct a {\n  n: u8\n  data: bytes[n]\n}").unwrap();
+        let err = decode(&template, &mut &[5u8, 1, 2][..], 0, None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 3: unexpected end of input reading `data` at offset 0x1 (2 of 5 bytes available)"
+        );
+
+        let template = parse("struct a {\n  data: bytes[missing]\n}").unwrap();
+        let err = decode(&template, &mut &[0u8][..], 0, None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 2: `missing` is not a decoded integer field"
+        );
+    }
+
+    #[test]
+    fn struct_arrays_bounded_by_input() {
+        let template = parse("struct a {\n  n: u64\n  xs: e[n]\n}\nstruct e {\n}").unwrap();
+        let data = b"\xff\xff\xff\xff\xff\xff\xff\x7f";
+        let err = decode(&template, &mut &data[..], 0, None).unwrap_err();
+        assert_eq!(err.to_string(), "line 3: `xs[0]` takes up no bytes");
+
+        let template = parse("struct a {\n  n: u8\n  xs: e[n]\n}\nstruct e {\n  x: u8\n}").unwrap();
+        let err = decode(&template, &mut &[200u8, 1, 2][..], 0, Some(3)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 3: unexpected end of input reading `xs` at offset 0x1 (2 bytes available for 200 elements)"
+        );
+    }
+}
@@ -0,0 +1,609 @@
+use super::TemplateError;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Prim {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+impl Prim {
+    pub fn size(self) -> usize {
+        match self {
+            Prim::U8 | Prim::I8 => 1,
+            Prim::U16 | Prim::I16 => 2,
+            Prim::U32 | Prim::I32 | Prim::F32 => 4,
+            Prim::U64 | Prim::I64 | Prim::F64 => 8,
+        }
+    }
+
+    pub fn is_integer(self) -> bool {
+        !matches!(self, Prim::F32 | Prim::F64)
+    }
+
+    /// Parse a primitive type name such as `u32`, `i16be` or `f64le`.
+    fn from_name(name: &str) -> Option<(Prim, Option<Endian>)> {
+        let (base, endian) = if let Some(base) = name.strip_suffix("le") {
+            (base, Some(Endian::Little))
+        } else if let Some(base) = name.strip_suffix("be") {
+            (base, Some(Endian::Big))
+        } else {
+            (name, None)
+        };
+        let prim = match base {
+            "u8" => Prim::U8,
+            "u16" => Prim::U16,
+            "u32" => Prim::U32,
+            "u64" => Prim::U64,
+            "i8" => Prim::I8,
+            "i16" => Prim::I16,
+            "i32" => Prim::I32,
+            "i64" => Prim::I64,
+            "f32" => Prim::F32,
+            "f64" => Prim::F64,
+            _ => return None,
+        };
+        Some((prim, endian))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TypeRef {
+    Prim(Prim, Option<Endian>),
+    /// Fixed-size text, `char[N]`.
+    Char,
+    /// Fixed-size raw bytes, `bytes[N]`.
+    Bytes,
+    /// Text preceded by its length, `string<u16>`.
+    LengthPrefixed(Prim, Option<Endian>),
+    Struct(String),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum UnOp {
+    Neg,
+    Not,
+    BitNot,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BinOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shl,
+    Shr,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Int(i128),
+    Name(String),
+    Unary(UnOp, Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldDef {
+    pub name: String,
+    pub ty: TypeRef,
+    pub count: Option<Expr>,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Item {
+    Field(FieldDef),
+    If {
+        cond: Expr,
+        then: Vec<Item>,
+        otherwise: Vec<Item>,
+        line: usize,
+    },
+    Endian(Endian),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StructDef {
+    pub name: String,
+    pub body: Vec<Item>,
+    pub line: usize,
+}
+
+/// A parsed template: a set of structs, the first of which (or the one named
+/// `main`) describes the start of the input.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    pub endian: Endian,
+    pub structs: Vec<StructDef>,
+}
+
+impl Template {
+    pub fn root(&self) -> &StructDef {
+        self.structs
+            .iter()
+            .find(|s| s.name == "main")
+            .unwrap_or(&self.structs[0])
+    }
+
+    pub fn find(&self, name: &str) -> Option<&StructDef> {
+        self.structs.iter().find(|s| s.name == name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Tok {
+    Ident(String),
+    Int(i128),
+    Punct(&'static str),
+    Eof,
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    tok: Tok,
+    line: usize,
+    column: usize,
+}
+
+// Longest first, so that `<=` wins over `<`.
+const PUNCTUATION: [&str; 29] = [
+    "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "{", "}", "[", "]", "(", ")", ":", ";", ",",
+    "<", ">", "+", "-", "*", "/", "%", "&", "|", "^", "!", "~",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>, TemplateError> {
+    let mut tokens = vec![];
+    for (idx, text) in source.lines().enumerate() {
+        let line = idx + 1;
+        let chars: Vec<char> = text.chars().collect();
+        let mut i = 0;
+        'line: while i < chars.len() {
+            let column = i + 1;
+            let ch = chars[i];
+            if ch.is_whitespace() {
+                i += 1;
+                continue;
+            }
+            if ch == '#' || (ch == '/' && chars.get(i + 1) == Some(&'/')) {
+                break;
+            }
+            if ch.is_ascii_alphabetic() || ch == '_' {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let ident = chars[start..i].iter().collect();
+                tokens.push(Token {
+                    tok: Tok::Ident(ident),
+                    line,
+                    column,
+                });
+                continue;
+            }
+            if ch.is_ascii_digit() {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let literal: String = chars[start..i].iter().filter(|&&c| c != '_').collect();
+                let parsed = if let Some(hex) = literal
+                    .strip_prefix("0x")
+                    .or_else(|| literal.strip_prefix("0X"))
+                {
+                    i128::from_str_radix(hex, 16)
+                } else if let Some(bin) = literal.strip_prefix("0b") {
+                    i128::from_str_radix(bin, 2)
+                } else if let Some(oct) = literal.strip_prefix("0o") {
+                    i128::from_str_radix(oct, 8)
+                } else {
+                    literal.parse()
+                };
+                let value = parsed.map_err(|_| {
+                    TemplateError::syntax(line, column, format!("invalid number `{}`", literal))
+                })?;
+                tokens.push(Token {
+                    tok: Tok::Int(value),
+                    line,
+                    column,
+                });
+                continue;
+            }
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            for punct in PUNCTUATION.iter() {
+                if rest.starts_with(punct) {
+                    tokens.push(Token {
+                        tok: Tok::Punct(punct),
+                        line,
+                        column,
+                    });
+                    i += punct.len();
+                    continue 'line;
+                }
+            }
+            return Err(TemplateError::syntax(
+                line,
+                column,
+                format!("unexpected character {:?}", ch),
+            ));
+        }
+    }
+    let line = source.lines().count().max(1);
+    tokens.push(Token {
+        tok: Tok::Eof,
+        line,
+        column: source.lines().last().map_or(0, |l| l.chars().count()) + 1,
+    });
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error<S: Into<String>>(&self, message: S) -> TemplateError {
+        let token = self.peek();
+        TemplateError::syntax(token.line, token.column, message)
+    }
+
+    fn describe(&self) -> String {
+        match &self.peek().tok {
+            Tok::Ident(s) => format!("`{}`", s),
+            Tok::Int(n) => format!("`{}`", n),
+            Tok::Punct(p) => format!("`{}`", p),
+            Tok::Eof => "end of template".to_string(),
+        }
+    }
+
+    fn is_punct(&self, punct: &str) -> bool {
+        matches!(self.peek().tok, Tok::Punct(p) if p == punct)
+    }
+
+    fn eat_punct(&mut self, punct: &str) -> bool {
+        if self.is_punct(punct) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_punct(&mut self, punct: &str) -> Result<(), TemplateError> {
+        if self.eat_punct(punct) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{}`, found {}", punct, self.describe())))
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.peek().tok, Tok::Ident(s) if s == keyword)
+    }
+
+    fn expect_ident(&mut self, what: &str) -> Result<String, TemplateError> {
+        match self.peek().tok.clone() {
+            Tok::Ident(s) => {
+                self.next();
+                Ok(s)
+            }
+            _ => Err(self.error(format!("expected {}, found {}", what, self.describe()))),
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        while self.eat_punct(";") || self.eat_punct(",") {}
+    }
+
+    fn template(&mut self) -> Result<Template, TemplateError> {
+        let mut endian = Endian::Little;
+        let mut structs: Vec<StructDef> = vec![];
+        loop {
+            self.skip_separators();
+            if self.peek().tok == Tok::Eof {
+                break;
+            }
+            if self.is_keyword("endian") {
+                endian = self.endian()?;
+            } else if self.is_keyword("struct") {
+                let def = self.struct_def()?;
+                if structs.iter().any(|s| s.name == def.name) {
+                    return Err(TemplateError::syntax(
+                        def.line,
+                        1,
+                        format!("struct `{}` is defined more than once", def.name),
+                    ));
+                }
+                structs.push(def);
+            } else {
+                return Err(self.error(format!(
+                    "expected `struct` or `endian`, found {}",
+                    self.describe()
+                )));
+            }
+        }
+        if structs.is_empty() {
+            return Err(self.error("template does not define any struct"));
+        }
+        let template = Template { endian, structs };
+        for def in &template.structs {
+            check_types(&template, &def.body)?;
+        }
+        Ok(template)
+    }
+
+    fn endian(&mut self) -> Result<Endian, TemplateError> {
+        self.next();
+        match self.expect_ident("`little` or `big`")?.as_str() {
+            "little" | "le" => Ok(Endian::Little),
+            "big" | "be" => Ok(Endian::Big),
+            other => {
+                let token = &self.tokens[self.pos - 1];
+                Err(TemplateError::syntax(
+                    token.line,
+                    token.column,
+                    format!("unknown endianness `{}`", other),
+                ))
+            }
+        }
+    }
+
+    fn struct_def(&mut self) -> Result<StructDef, TemplateError> {
+        let line = self.next().line;
+        let name = self.expect_ident("struct name")?;
+        let body = self.block()?;
+        Ok(StructDef { name, body, line })
+    }
+
+    fn block(&mut self) -> Result<Vec<Item>, TemplateError> {
+        self.expect_punct("{")?;
+        let mut items = vec![];
+        loop {
+            self.skip_separators();
+            if self.eat_punct("}") {
+                return Ok(items);
+            }
+            if self.peek().tok == Tok::Eof {
+                return Err(self.error("expected `}`, found end of template"));
+            }
+            items.push(self.item()?);
+        }
+    }
+
+    fn item(&mut self) -> Result<Item, TemplateError> {
+        if self.is_keyword("endian") {
+            return Ok(Item::Endian(self.endian()?));
+        }
+        if self.is_keyword("if") {
+            let line = self.next().line;
+            let cond = self.expr()?;
+            let then = self.block()?;
+            let otherwise = if self.is_keyword("else") {
+                self.next();
+                if self.is_keyword("if") {
+                    vec![self.item()?]
+                } else {
+                    self.block()?
+                }
+            } else {
+                vec![]
+            };
+            return Ok(Item::If {
+                cond,
+                then,
+                otherwise,
+                line,
+            });
+        }
+
+        let (line, column) = (self.peek().line, self.peek().column);
+        let name = self.expect_ident("field name")?;
+        self.expect_punct(":")?;
+        let ty = self.type_ref()?;
+        let count = if self.eat_punct("[") {
+            let count = self.expr()?;
+            self.expect_punct("]")?;
+            Some(count)
+        } else {
+            None
+        };
+        if count.is_none() && (ty == TypeRef::Char || ty == TypeRef::Bytes) {
+            return Err(TemplateError::syntax(
+                line,
+                self.tokens[self.pos - 1].column,
+                format!("field `{}` needs a length, e.g. `[4]`", name),
+            ));
+        }
+        Ok(Item::Field(FieldDef {
+            name,
+            ty,
+            count,
+            line,
+            column,
+        }))
+    }
+
+    fn type_ref(&mut self) -> Result<TypeRef, TemplateError> {
+        let token = self.peek().clone();
+        let name = self.expect_ident("type")?;
+        if let Some((prim, endian)) = Prim::from_name(&name) {
+            return Ok(TypeRef::Prim(prim, endian));
+        }
+        match name.as_str() {
+            "char" => Ok(TypeRef::Char),
+            "bytes" => Ok(TypeRef::Bytes),
+            "string" => {
+                self.expect_punct("<")?;
+                let token = self.peek().clone();
+                let prefix = self.expect_ident("length type")?;
+                self.expect_punct(">")?;
+                match Prim::from_name(&prefix) {
+                    Some((prim, endian)) if prim.is_integer() => {
+                        Ok(TypeRef::LengthPrefixed(prim, endian))
+                    }
+                    _ => Err(TemplateError::syntax(
+                        token.line,
+                        token.column,
+                        format!("`{}` is not an integer type", prefix),
+                    )),
+                }
+            }
+            "struct" | "if" | "else" | "endian" => Err(TemplateError::syntax(
+                token.line,
+                token.column,
+                format!("expected type, found keyword `{}`", name),
+            )),
+            _ => Ok(TypeRef::Struct(name)),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, TemplateError> {
+        self.binary(0)
+    }
+
+    fn binary(&mut self, min_prec: u8) -> Result<Expr, TemplateError> {
+        let mut lhs = self.unary()?;
+        while let Tok::Punct(p) = self.peek().tok {
+            let (op, prec) = match binop(p) {
+                Some(op) => op,
+                None => break,
+            };
+            if prec < min_prec {
+                break;
+            }
+            self.next();
+            let rhs = self.binary(prec + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, TemplateError> {
+        let op = if self.eat_punct("-") {
+            Some(UnOp::Neg)
+        } else if self.eat_punct("!") {
+            Some(UnOp::Not)
+        } else if self.eat_punct("~") {
+            Some(UnOp::BitNot)
+        } else {
+            None
+        };
+        if let Some(op) = op {
+            return Ok(Expr::Unary(op, Box::new(self.unary()?)));
+        }
+
+        match self.peek().tok.clone() {
+            Tok::Int(n) => {
+                self.next();
+                Ok(Expr::Int(n))
+            }
+            Tok::Ident(name) => {
+                self.next();
+                Ok(Expr::Name(name))
+            }
+            Tok::Punct("(") => {
+                self.next();
+                let inner = self.expr()?;
+                self.expect_punct(")")?;
+                Ok(inner)
+            }
+            _ => Err(self.error(format!("expected expression, found {}", self.describe()))),
+        }
+    }
+}
+
+fn binop(punct: &str) -> Option<(BinOp, u8)> {
+    Some(match punct {
+        "||" => (BinOp::Or, 1),
+        "&&" => (BinOp::And, 2),
+        "==" => (BinOp::Eq, 3),
+        "!=" => (BinOp::Ne, 3),
+        "<" => (BinOp::Lt, 4),
+        "<=" => (BinOp::Le, 4),
+        ">" => (BinOp::Gt, 4),
+        ">=" => (BinOp::Ge, 4),
+        "|" => (BinOp::BitOr, 5),
+        "^" => (BinOp::BitXor, 6),
+        "&" => (BinOp::BitAnd, 7),
+        "<<" => (BinOp::Shl, 8),
+        ">>" => (BinOp::Shr, 8),
+        "+" => (BinOp::Add, 9),
+        "-" => (BinOp::Sub, 9),
+        "*" => (BinOp::Mul, 10),
+        "/" => (BinOp::Div, 10),
+        "%" => (BinOp::Rem, 10),
+        _ => return None,
+    })
+}
+
+fn check_types(template: &Template, items: &[Item]) -> Result<(), TemplateError> {
+    for item in items {
+        match item {
+            Item::Field(field) => {
+                if let TypeRef::Struct(name) = &field.ty {
+                    if template.find(name).is_none() {
+                        return Err(TemplateError::syntax(
+                            field.line,
+                            field.column,
+                            format!("unknown type `{}` for field `{}`", name, field.name),
+                        ));
+                    }
+                }
+            }
+            Item::If {
+                then, otherwise, ..
+            } => {
+                check_types(template, then)?;
+                check_types(template, otherwise)?;
+            }
+            Item::Endian(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Parse template source into a `Template`.
+pub fn parse(source: &str) -> Result<Template, TemplateError> {
+    let tokens = tokenize(source)?;
+    Parser { tokens, pos: 0 }.template()
+}